@@ -5,25 +5,41 @@ import os
 def process_dictionary(input_file, output_file):
     print(f"Processing {input_file}...")
     
-    words = set()
+    # word -> score (None for plain `WORD` lines). Broda lines are `WORD;SCORE`.
+    words = {}
     try:
         with open(input_file, 'r', encoding='utf-8') as f:
             for line in f:
-                word = line.strip().upper()
+                word, _, raw_score = line.strip().partition(';')
+                word = word.strip().upper()
+                score = None
+                if raw_score.strip():
+                    try:
+                        # The solver stores scores as a byte, 0-255
+                        score = min(max(int(raw_score), 0), 255)
+                    except ValueError:
+                        continue
                 # Filter: 3-15 chars, only A-Z
                 if 3 <= len(word) <= 15 and word.isalpha():
-                    words.add(word)
+                    # Duplicates keep their best score
+                    previous = words.get(word)
+                    if previous is None or (score is not None and score > previous):
+                        words[word] = score
     except FileNotFoundError:
         print(f"Error: Input file {input_file} not found.")
         return
 
-    sorted_words = sorted(list(words))
+    sorted_words = sorted(words)
     print(f"Found {len(sorted_words)} valid words.")
 
-    # For now, just dump as JSON. 
-    # Later we can implement the DAWG compression here.
+    # JSON is what the client loads today. The binary DAWG blob is produced
+    # by the Rust packer: `cargo run --bin pack_dictionary <input_txt> <out>`
+    # from solver/.
     with open(output_file, 'w', encoding='utf-8') as f:
-        json.dump(sorted_words, f)
+        # Scored words are emitted as `WORD;SCORE`, which the solver's
+        # add_words accepts directly.
+        entries = [w if words[w] is None else f"{w};{words[w]}" for w in sorted_words]
+        json.dump(entries, f)
     
     print(f"Written to {output_file}")
 
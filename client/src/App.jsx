@@ -23,12 +23,23 @@ function App() {
       .catch(err => console.error("Failed to load dictionary", err));
   }, []);
 
+  // Hand the word list to the solver session once; every solve reuses it.
+  useEffect(() => {
+    if (solver && dictionary.length > 0) {
+      solver.add_words(dictionary);
+    }
+  }, [solver, dictionary]);
+
   const { grid, cursor, activeWordCells, actions } = useGrid(generatedConfig || { size: samplePuzzle.size, blackCells: samplePuzzle.blackCells });
 
   const handleGenerate = () => {
     if (!solver) return;
     const json = solver.generate_grid(15, 15);
     const config = JSON.parse(json);
+    if (config.status !== 'success') {
+        alert("Could not generate a layout: " + config.error.message);
+        return;
+    }
     
     // Convert array of [r,c] to Set of "r,c" strings
     const blackCells = new Set(config.black_cells.map(([r, c]) => `${r},${c}`));
@@ -66,13 +77,17 @@ function App() {
         fixed_cells
     };
     
-    const resultJson = solver.solve(JSON.stringify(config), dictionary);
+    const resultJson = solver.solve(JSON.stringify(config));
     const result = JSON.parse(resultJson);
     
     if (result.status === 'success') {
         actions.setGridData(result.grid);
+    } else if (result.status === 'gave_up') {
+        // Show how far the solver got before its budget ran out
+        actions.setGridData(result.grid);
+        alert("Solver gave up: " + result.error.message);
     } else {
-        alert("Solver failed: " + result.message);
+        alert("Solver failed: " + result.error.message);
     }
   };
 
@@ -0,0 +1,145 @@
+use crate::dawg::Dawg;
+use crate::pattern::{Collector, MatchOrder, PatternError, WordMatches};
+use serde::Deserialize;
+
+// A dictionary lookup by letters rather than positions: the words the
+// tiles spell, using all of them (anagrams) or any of them (`partial`).
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AnagramQuery {
+    pub letters: String, // The tiles, `?` for a blank
+    pub blanks: usize,   // Blanks on top of any `?` in `letters`
+    pub partial: bool,
+    pub min_length: Option<usize>, // Shortest word when partial
+    pub min_score: u8,
+    pub order: MatchOrder,
+    pub limit: Option<usize>,
+}
+
+pub fn find_anagrams(dawg: &Dawg, query: &AnagramQuery) -> Result<WordMatches, PatternError> {
+    let mut letters = [0u8; 26];
+    let mut blanks = query.blanks;
+    for (at, ch) in query.letters.chars().enumerate() {
+        if ch == '?' {
+            blanks = blanks.saturating_add(1);
+        } else if ch.is_ascii_alphabetic() {
+            let label = (ch.to_ascii_uppercase() as u8 - b'A') as usize;
+            letters[label] = letters[label].saturating_add(1);
+        } else {
+            return Err(PatternError::UnexpectedChar { at, ch });
+        }
+    }
+    let tiles = letters.iter().map(|&n| n as usize).sum::<usize>().saturating_add(blanks);
+    let min_length = if query.partial { query.min_length.unwrap_or(1) } else { tiles };
+
+    let mut found = Collector::new(query.min_score, query.order.into(), query.limit);
+    if tiles > 0 && min_length <= tiles {
+        dawg.walk_anagrams(letters, blanks, min_length..=tiles, &mut |word, score| found.visit(word, score));
+    }
+    Ok(found.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::MatchOrder;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    // Words of one to six letters over A-E, so most tiles spell a few.
+    fn words(seed: u64) -> Vec<String> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut words: Vec<String> = (0..500)
+            .map(|_| (0..rng.gen_range(1..=6)).map(|_| (b'A' + rng.gen_range(0..5)) as char).collect())
+            .collect();
+        words.sort();
+        words.dedup();
+        words
+    }
+
+    // Whether `word` can be spelled from the tiles, blanks standing in
+    // for whatever letters are missing.
+    fn spells(word: &str, tiles: &str, blanks: usize) -> bool {
+        let mut left: Vec<char> = tiles.chars().collect();
+        let mut missing = 0;
+        for ch in word.chars() {
+            match left.iter().position(|&tile| tile == ch) {
+                Some(at) => {
+                    left.remove(at);
+                }
+                None => missing += 1,
+            }
+        }
+        missing <= blanks
+    }
+
+    fn found(dawg: &Dawg, query: &AnagramQuery) -> Vec<String> {
+        find_anagrams(dawg, query).unwrap().words.into_iter().map(|w| w.word).collect()
+    }
+
+    #[test]
+    fn finds_the_words_the_tiles_spell() {
+        let words = words(19);
+        let dawg = Dawg::from_words(&words);
+        let mut total = 0;
+        for (letters, extra) in [("ABCD", 0), ("AABE", 0), ("AB?", 0), ("CDE", 1), ("??", 1), ("ABCDEA", 0), ("", 2)] {
+            let tiles: String = letters.chars().filter(|&ch| ch != '?').collect();
+            let blanks = extra + letters.len() - tiles.len();
+            let count = tiles.len() + blanks;
+            for (partial, min_length) in [(false, None), (true, None), (true, Some(3))] {
+                let shortest = if partial { min_length.unwrap_or(1) } else { count };
+                let expected: Vec<String> = words
+                    .iter()
+                    .filter(|w| (shortest..=count).contains(&w.len()) && spells(w, &tiles, blanks))
+                    .cloned()
+                    .collect();
+                let query = AnagramQuery {
+                    letters: letters.to_lowercase(),
+                    blanks: extra,
+                    partial,
+                    min_length,
+                    ..AnagramQuery::default()
+                };
+                assert_eq!(found(&dawg, &query), expected, "{} {} {}", letters, blanks, partial);
+                total += expected.len();
+            }
+        }
+        assert!(total > 50);
+        assert!(found(&dawg, &AnagramQuery::default()).is_empty());
+    }
+
+    #[test]
+    fn anagram_results_rank_and_limit_like_patterns() {
+        let scored: Vec<(String, u8)> = words(19).into_iter().enumerate().map(|(i, w)| (w, (i * 37 % 256) as u8)).collect();
+        let dawg = Dawg::from_scored_words(scored.iter().map(|(w, score)| (w, *score)));
+        let query = AnagramQuery {
+            letters: "ABCDE".to_string(),
+            partial: true,
+            min_score: 30,
+            order: MatchOrder::Score,
+            limit: Some(4),
+            ..AnagramQuery::default()
+        };
+        let mut expected: Vec<(String, u8)> = scored
+            .iter()
+            .filter(|(w, score)| *score >= 30 && spells(w, "ABCDE", 0))
+            .cloned()
+            .collect();
+        expected.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        let matches = find_anagrams(&dawg, &query).unwrap();
+        let words: Vec<(String, u8)> = matches.words.into_iter().map(|w| (w.word, w.score)).collect();
+        assert_eq!(words, expected[..4]);
+        assert!(!matches.complete);
+        assert_eq!(
+            find_anagrams(&dawg, &AnagramQuery { letters: "AB1".to_string(), ..query }).err(),
+            Some(PatternError::UnexpectedChar { at: 2, ch: '1' })
+        );
+    }
+
+    #[test]
+    fn anagram_queries_have_no_distance_order() {
+        let query = |json: &str| serde_json::from_str::<AnagramQuery>(json).map(|query| query.order).ok();
+        assert_eq!(query(r#"{ "letters": "TAC", "order": "score" }"#), Some(MatchOrder::Score));
+        assert_eq!(query(r#"{ "letters": "TAC", "order": "distance" }"#), None);
+    }
+}
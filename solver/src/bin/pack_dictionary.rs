@@ -0,0 +1,43 @@
+// Offline factory: packs a word list into the binary DAWG blob the solver
+// loads at runtime. Accepts one entry per line, either `WORD` or the Broda
+// `WORD;SCORE` form.
+use lacuna_solver::dawg::{parse_entry, Dawg};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: pack_dictionary <input_txt> <output_dawg>");
+        return ExitCode::FAILURE;
+    }
+
+    println!("Processing {}...", args[1]);
+    let text = match std::fs::read_to_string(&args[1]) {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error: could not read {}: {}", args[1], e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Same filter as process_dictionary.py: 3-15 chars, only A-Z
+    let entries = text.lines().filter_map(parse_entry).filter(|(w, _)| {
+        (3..=15).contains(&w.len()) && w.chars().all(|c| c.is_ascii_alphabetic())
+    });
+
+    let dawg = Dawg::from_scored_words(entries);
+    let blob = dawg.to_bytes();
+    println!(
+        "Found {} valid words ({} nodes, {} edges).",
+        dawg.word_count,
+        dawg.nodes.len(),
+        dawg.edges.len()
+    );
+
+    if let Err(e) = std::fs::write(&args[2], &blob) {
+        eprintln!("Error: could not write {}: {}", args[2], e);
+        return ExitCode::FAILURE;
+    }
+    println!("Written {} bytes to {}", blob.len(), args[2]);
+    ExitCode::SUCCESS
+}
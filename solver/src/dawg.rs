@@ -1,72 +1,1019 @@
-use std::collections::HashMap;
+use crate::domain::Domain;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
 
-#[derive(Default, Debug, Clone)]
+// A state in the graph. Its outgoing edges are the contiguous run
+// `edges[first_edge..first_edge + edge_count]`, sorted by label.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct DawgNode {
-    pub children: HashMap<char, usize>, // char -> index in nodes vector
+    pub first_edge: u32,
+    pub edge_count: u8,
     pub is_terminal: bool,
+    pub score: u8, // Only meaningful on terminals
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DawgEdge {
+    pub label: u8, // 0..26 for A..Z
+    pub target: u32,
+}
+
+impl DawgEdge {
+    pub fn letter(&self) -> char {
+        (b'A' + self.label) as char
+    }
+}
+
+// Score given to words that arrive without one (plain `WORD` lines).
+pub const DEFAULT_SCORE: u8 = 50;
+
+// Minimized DAWG: equivalent subtrees (same terminal flag and score, same
+// labelled edges to the same targets) are stored once, so shared suffixes
+// like -ING or -TION collapse into a single chain of nodes.
 #[derive(Debug, Clone)]
 pub struct Dawg {
     pub nodes: Vec<DawgNode>,
+    pub edges: Vec<DawgEdge>,
+    pub word_count: usize,
+    pub max_length: usize, // Letters in the longest word
+}
+
+impl Default for Dawg {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Dawg {
     pub fn new() -> Self {
         Dawg {
             nodes: vec![DawgNode::default()], // Root is at index 0
+            edges: Vec::new(),
+            word_count: 0,
+            max_length: 0,
         }
     }
 
-    pub fn insert(&mut self, word: &str) {
-        let mut node_idx = 0;
-        for c in word.chars() {
-            let c = c.to_ascii_uppercase();
-            if !self.nodes[node_idx].children.contains_key(&c) {
-                let new_node_idx = self.nodes.len();
-                self.nodes.push(DawgNode::default());
-                self.nodes[node_idx].children.insert(c, new_node_idx);
-            }
-            node_idx = *self.nodes[node_idx].children.get(&c).unwrap();
+    // Builds a minimized graph from an arbitrary word list. Words are
+    // upper-cased, deduplicated and sorted first; anything that is empty or
+    // contains a character outside A-Z is skipped.
+    pub fn from_words<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::from_scored_words(words.into_iter().map(|w| (w, DEFAULT_SCORE)))
+    }
+
+    // Like `from_words`, with a score per word. A word listed more than once
+    // keeps its highest score.
+    pub fn from_scored_words<I, S>(words: I) -> Self
+    where
+        I: IntoIterator<Item = (S, u8)>,
+        S: AsRef<str>,
+    {
+        let mut builder = DawgBuilder::new();
+        for (word, score) in &sorted_unique(words) {
+            builder.insert(word, *score);
         }
-        self.nodes[node_idx].is_terminal = true;
+        builder.finish()
     }
 
-    pub fn is_word(&self, word: &str) -> bool {
-        let mut node_idx = 0;
-        for c in word.chars() {
-            let c = c.to_ascii_uppercase();
-            match self.nodes[node_idx].children.get(&c) {
-                Some(&idx) => node_idx = idx,
-                None => return false,
+    // This graph's words plus `other`'s. Both come out in order, so they
+    // are merged straight into the new graph without sorting; a word in
+    // both keeps its higher score. The graph is still rebuilt, so the cost
+    // grows with the total word count.
+    pub fn merged(&self, other: &Dawg) -> Self {
+        merge_sorted(self.labelled_words(), other.labelled_words())
+    }
+
+    // Like `merged`, for a list of words cleaned up as in `from_words`.
+    // Only the new words are sorted.
+    pub fn with_scored_words<I, S>(&self, words: I) -> Self
+    where
+        I: IntoIterator<Item = (S, u8)>,
+        S: AsRef<str>,
+    {
+        merge_sorted(self.labelled_words(), sorted_unique(words))
+    }
+
+    // This graph without `words`, in any case. Also a rebuild.
+    pub fn without_words<I, S>(&self, words: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let removed: HashSet<Vec<u8>> = words.into_iter().filter_map(|w| normalize(w.as_ref())).collect();
+        let mut builder = DawgBuilder::new();
+        for (word, score) in self.labelled_words() {
+            if !removed.contains(&word) {
+                builder.insert(&word, score);
             }
         }
-        self.nodes[node_idx].is_terminal
+        builder.finish()
     }
 
-    pub fn is_valid_prefix(&self, prefix: &str) -> bool {
+    // Builds from Broda-style lines: `WORD` or `WORD;SCORE`.
+    pub fn from_entries<I, S>(lines: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::from_scored_words(lines.into_iter().filter_map(|l| {
+            let (word, score) = parse_entry(l.as_ref())?;
+            Some((word.to_string(), score))
+        }))
+    }
+
+    // Every word in the graph, in lexicographic order.
+    pub fn words(&self) -> Vec<String> {
+        self.scored_words().into_iter().map(|(w, _)| w).collect()
+    }
+
+    // Every word with its score, in lexicographic order.
+    pub fn scored_words(&self) -> Vec<(String, u8)> {
+        self.labelled_words()
+            .into_iter()
+            .map(|(word, score)| (word.iter().map(|&label| (b'A' + label) as char).collect(), score))
+            .collect()
+    }
+
+    // Same, with words as letter indices.
+    fn labelled_words(&self) -> Vec<(Vec<u8>, u8)> {
+        let mut out = Vec::with_capacity(self.word_count);
+        self.walk_matching(&[], 0..=usize::MAX, &mut |word, score| {
+            out.push((word.to_vec(), score));
+            true
+        });
+        out
+    }
+
+    // Bytes held by the node and edge tables.
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<DawgNode>()
+            + self.edges.capacity() * std::mem::size_of::<DawgEdge>()
+    }
+
+    pub fn edges(&self, node_idx: usize) -> &[DawgEdge] {
+        let node = &self.nodes[node_idx];
+        let start = node.first_edge as usize;
+        &self.edges[start..start + node.edge_count as usize]
+    }
+
+    pub fn child(&self, node_idx: usize, c: char) -> Option<usize> {
+        let c = c.to_ascii_uppercase();
+        if !c.is_ascii_uppercase() {
+            return None;
+        }
+        let label = c as u8 - b'A';
+        let edges = self.edges(node_idx);
+        edges
+            .binary_search_by_key(&label, |e| e.label)
+            .ok()
+            .map(|i| edges[i].target as usize)
+    }
+
+    // Follows `prefix` from the root, returning the node it ends on.
+    fn walk(&self, prefix: &str) -> Option<usize> {
         let mut node_idx = 0;
         for c in prefix.chars() {
-            let c = c.to_ascii_uppercase();
-            match self.nodes[node_idx].children.get(&c) {
-                Some(&idx) => node_idx = idx,
-                None => return false,
-            }
+            node_idx = self.child(node_idx, c)?;
         }
-        true
+        Some(node_idx)
+    }
+
+    pub fn is_word(&self, word: &str) -> bool {
+        match self.walk(word) {
+            Some(idx) => self.nodes[idx].is_terminal,
+            None => false,
+        }
+    }
+
+    pub fn score(&self, word: &str) -> Option<u8> {
+        let node = &self.nodes[self.walk(word)?];
+        if node.is_terminal {
+            Some(node.score)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_valid_prefix(&self, prefix: &str) -> bool {
+        self.walk(prefix).is_some()
     }
-    
+
     // Returns the set of valid next characters for a given prefix
     // This is crucial for the domain intersection logic
     pub fn next_chars(&self, prefix: &str) -> Option<Vec<char>> {
-        let mut node_idx = 0;
-        for c in prefix.chars() {
+        let node_idx = self.walk(prefix)?;
+        Some(self.edges(node_idx).iter().map(|e| e.letter()).collect())
+    }
+
+    // Visits, in lexicographic order, the words with a length in `lengths`
+    // whose every letter is in the domain for its position (positions past
+    // the end of `domains` take any letter). `visit` gets the word as
+    // letter indices and its score, and returns false to stop the walk.
+    pub fn walk_matching<F>(&self, domains: &[Domain], lengths: RangeInclusive<usize>, visit: &mut F)
+    where
+        F: FnMut(&[u8], u8) -> bool,
+    {
+        // No word runs past the longest, however long the range
+        let mut path = Vec::with_capacity((*lengths.end()).min(self.max_length));
+        self.walk_matching_from(0, domains, &lengths, &mut path, visit);
+    }
+
+    fn walk_matching_from<F>(
+        &self,
+        node_idx: usize,
+        domains: &[Domain],
+        lengths: &RangeInclusive<usize>,
+        path: &mut Vec<u8>,
+        visit: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&[u8], u8) -> bool,
+    {
+        let node = &self.nodes[node_idx];
+        if node.is_terminal && lengths.contains(&path.len()) && !visit(path, node.score) {
+            return false;
+        }
+        if path.len() >= *lengths.end() {
+            return true;
+        }
+        let allowed = domains.get(path.len()).copied().unwrap_or_else(Domain::full);
+        for edge in self.edges(node_idx) {
+            if !allowed.contains(edge.letter()) {
+                continue;
+            }
+            path.push(edge.label);
+            let go_on = self.walk_matching_from(edge.target as usize, domains, lengths, path, visit);
+            path.pop();
+            if !go_on {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Visits, in lexicographic order, the words with a length in `lengths`
+    // that can be spelled from the tiles: `letters` counts the tiles of each
+    // letter, and each of the `blanks` can stand for any letter. `visit`
+    // gets the word as letter indices and its score, and returns false to
+    // stop the walk.
+    pub fn walk_anagrams<F>(&self, mut letters: [u8; 26], blanks: usize, lengths: RangeInclusive<usize>, visit: &mut F)
+    where
+        F: FnMut(&[u8], u8) -> bool,
+    {
+        let tiles = letters.iter().map(|&n| n as usize).sum::<usize>().saturating_add(blanks);
+        let max_length = (*lengths.end()).min(tiles).min(self.max_length);
+        let mut path = Vec::with_capacity(max_length);
+        self.walk_anagrams_from(0, &mut letters, blanks, &(*lengths.start()..=max_length), &mut path, visit);
+    }
+
+    fn walk_anagrams_from<F>(
+        &self,
+        node_idx: usize,
+        letters: &mut [u8; 26],
+        blanks: usize,
+        lengths: &RangeInclusive<usize>,
+        path: &mut Vec<u8>,
+        visit: &mut F,
+    ) -> bool
+    where
+        F: FnMut(&[u8], u8) -> bool,
+    {
+        let node = &self.nodes[node_idx];
+        if node.is_terminal && lengths.contains(&path.len()) && !visit(path, node.score) {
+            return false;
+        }
+        if path.len() >= *lengths.end() {
+            return true;
+        }
+        for edge in self.edges(node_idx) {
+            let label = edge.label as usize;
+            // A blank only where no tile of the letter is left; using the
+            // tile instead never spells fewer words
+            let go_on = if letters[label] > 0 {
+                letters[label] -= 1;
+                path.push(edge.label);
+                let go_on = self.walk_anagrams_from(edge.target as usize, letters, blanks, lengths, path, visit);
+                path.pop();
+                letters[label] += 1;
+                go_on
+            } else if blanks > 0 {
+                path.push(edge.label);
+                let go_on = self.walk_anagrams_from(edge.target as usize, letters, blanks - 1, lengths, path, visit);
+                path.pop();
+                go_on
+            } else {
+                true
+            };
+            if !go_on {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Visits, in lexicographic order, the words with a length in `lengths`
+    // within `max_distance` edits (letters inserted, deleted or replaced)
+    // of `target`, given as letter indices. `visit` gets the word as letter
+    // indices, its score and its distance, and returns false to stop the
+    // walk.
+    pub fn walk_similar<F>(&self, target: &[u8], max_distance: usize, lengths: RangeInclusive<usize>, visit: &mut F)
+    where
+        F: FnMut(&[u8], u8, usize) -> bool,
+    {
+        let max_length = (*lengths.end())
+            .min(target.len().saturating_add(max_distance))
+            .min(self.max_length);
+        let bounds = SimilarTo {
+            target,
+            max_distance,
+            lengths: *lengths.start()..=max_length,
+        };
+        let mut path = Vec::with_capacity(max_length);
+        // Edit distances from the empty prefix to each prefix of the target
+        let row: Vec<usize> = (0..=target.len()).collect();
+        self.walk_similar_from(0, &bounds, &row, &mut path, visit);
+    }
+
+    // `row[j]` is the edit distance between `path` and the first `j`
+    // letters of the target.
+    fn walk_similar_from<F>(&self, node_idx: usize, bounds: &SimilarTo, row: &[usize], path: &mut Vec<u8>, visit: &mut F) -> bool
+    where
+        F: FnMut(&[u8], u8, usize) -> bool,
+    {
+        let node = &self.nodes[node_idx];
+        let distance = row[bounds.target.len()];
+        if node.is_terminal
+            && distance <= bounds.max_distance
+            && bounds.lengths.contains(&path.len())
+            && !visit(path, node.score, distance)
+        {
+            return false;
+        }
+        if path.len() >= *bounds.lengths.end() {
+            return true;
+        }
+        let mut next = vec![0; row.len()];
+        for edge in self.edges(node_idx) {
+            next[0] = row[0] + 1;
+            for j in 1..row.len() {
+                let replace = row[j - 1] + usize::from(bounds.target[j - 1] != edge.label);
+                next[j] = replace.min(row[j] + 1).min(next[j - 1] + 1);
+            }
+            // Distances never shrink as the path grows
+            if next.iter().min().is_some_and(|&d| d > bounds.max_distance) {
+                continue;
+            }
+            path.push(edge.label);
+            let go_on = self.walk_similar_from(edge.target as usize, bounds, &next, path, visit);
+            path.pop();
+            if !go_on {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// What `walk_similar` is looking for.
+struct SimilarTo<'a> {
+    target: &'a [u8],
+    max_distance: usize,
+    lengths: RangeInclusive<usize>,
+}
+
+// Splits a Broda list line (`WORD` or `WORD;SCORE`) into word and score.
+// Scores above 255 are clamped; an unparseable score rejects the line.
+pub fn parse_entry(line: &str) -> Option<(&str, u8)> {
+    let mut parts = line.splitn(2, ';');
+    let word = parts.next()?.trim();
+    if word.is_empty() {
+        return None;
+    }
+    let score = match parts.next() {
+        Some(raw) => raw.trim().parse::<u32>().ok()?.min(u8::MAX as u32) as u8,
+        None => DEFAULT_SCORE,
+    };
+    Some((word, score))
+}
+
+// Words as letter indices, sorted, each once with its highest score.
+fn sorted_unique<I, S>(words: I) -> Vec<(Vec<u8>, u8)>
+where
+    I: IntoIterator<Item = (S, u8)>,
+    S: AsRef<str>,
+{
+    let mut normalized: Vec<(Vec<u8>, u8)> = words
+        .into_iter()
+        .filter_map(|(w, score)| normalize(w.as_ref()).map(|w| (w, score)))
+        .collect();
+    // Highest score first within each word, so dedup keeps it
+    normalized.sort_unstable_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    normalized.dedup_by(|a, b| a.0 == b.0);
+    normalized
+}
+
+// Builds a graph from two sorted lists of unique words.
+fn merge_sorted(a: Vec<(Vec<u8>, u8)>, b: Vec<(Vec<u8>, u8)>) -> Dawg {
+    let mut builder = DawgBuilder::new();
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if x.0 == y.0 => {
+                let score = x.1.max(y.1);
+                b.next();
+                a.next().map(|(word, _)| (word, score))
+            }
+            (Some(x), Some(y)) if x.0 > y.0 => b.next(),
+            (Some(_), _) => a.next(),
+            (None, _) => b.next(),
+        };
+        let Some((word, score)) = next else {
+            break;
+        };
+        builder.insert(&word, score);
+    }
+    builder.finish()
+}
+
+// Maps a word onto letter indices 0..26, or None if it can't be stored.
+fn normalize(word: &str) -> Option<Vec<u8>> {
+    if word.is_empty() {
+        return None;
+    }
+    word.chars()
+        .map(|c| {
             let c = c.to_ascii_uppercase();
-            match self.nodes[node_idx].children.get(&c) {
-                Some(&idx) => node_idx = idx,
-                None => return None,
+            if c.is_ascii_uppercase() {
+                Some(c as u8 - b'A')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+struct PendingNode {
+    edges: Vec<(u8, u32)>,
+    is_terminal: bool,
+    score: u8,
+}
+
+// Incremental minimization over sorted input (Daciuk et al., 2000).
+// `pending` holds the not-yet-frozen path of the previous word; once a new
+// word diverges from it, everything below the divergence point can no
+// longer change and is frozen, reusing an equivalent node from `register`
+// whenever one already exists.
+struct DawgBuilder {
+    dawg: Dawg,
+    register: HashMap<PendingNode, u32>,
+    pending: Vec<PendingNode>,
+    previous: Vec<u8>,
+}
+
+impl DawgBuilder {
+    fn new() -> Self {
+        DawgBuilder {
+            dawg: Dawg::new(), // Slot 0 is reserved for the root, written in finish()
+            register: HashMap::new(),
+            pending: vec![PendingNode::default()],
+            previous: Vec::new(),
+        }
+    }
+
+    // `word` must sort strictly after every previously inserted word.
+    fn insert(&mut self, word: &[u8], score: u8) {
+        debug_assert!(word > self.previous.as_slice());
+
+        let common = word
+            .iter()
+            .zip(self.previous.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.collapse(common);
+
+        for _ in common..word.len() {
+            self.pending.push(PendingNode::default());
+        }
+        let last = self.pending.last_mut().unwrap();
+        last.is_terminal = true;
+        last.score = score;
+        self.previous = word.to_vec();
+        self.dawg.word_count += 1;
+        self.dawg.max_length = self.dawg.max_length.max(word.len());
+    }
+
+    // Freezes pending nodes until only the first `depth` letters remain open.
+    fn collapse(&mut self, depth: usize) {
+        while self.pending.len() > depth + 1 {
+            let node = self.pending.pop().unwrap();
+            let target = self.freeze(node);
+            let label = self.previous[self.pending.len() - 1];
+            self.pending.last_mut().unwrap().edges.push((label, target));
+        }
+    }
+
+    fn freeze(&mut self, node: PendingNode) -> u32 {
+        if let Some(&idx) = self.register.get(&node) {
+            return idx;
+        }
+        let idx = self.dawg.nodes.len() as u32;
+        let frozen = self.push_edges(&node);
+        self.dawg.nodes.push(frozen);
+        self.register.insert(node, idx);
+        idx
+    }
+
+    fn push_edges(&mut self, node: &PendingNode) -> DawgNode {
+        let first_edge = self.dawg.edges.len() as u32;
+        for &(label, target) in &node.edges {
+            self.dawg.edges.push(DawgEdge { label, target });
+        }
+        DawgNode {
+            first_edge,
+            edge_count: node.edges.len() as u8,
+            is_terminal: node.is_terminal,
+            score: node.score,
+        }
+    }
+
+    fn finish(mut self) -> Dawg {
+        self.collapse(0);
+        let root = self.pending.pop().unwrap();
+        self.dawg.nodes[0] = self.push_edges(&root);
+        self.dawg
+    }
+}
+
+// Binary dictionary format. All integers are little-endian.
+//
+//   magic        4 bytes  "LDWG"
+//   version      u16
+//   alphabet     u8 length followed by the letters, in label order
+//   word_count   u32
+//   node_count   u32
+//   edge_count   u32
+//   checksum     u32      FNV-1a over the node and edge tables
+//   nodes        node_count * (first_edge u32, edge_count u8, flags u8, score u8)
+//   edges        edge_count * (label u8, target u32)
+//
+// The tables are the in-memory arrays laid out flat, so loading a blob is a
+// single validating pass with no re-insertion or re-minimization. It is not
+// zero-copy: the records are decoded into the graph's own node and edge
+// vectors, which costs about the blob's size in memory while loading.
+pub const FORMAT_MAGIC: [u8; 4] = *b"LDWG";
+pub const FORMAT_VERSION: u16 = 2; // v2 added per-terminal scores
+const ALPHABET: &[u8; 26] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const NODE_RECORD_LEN: usize = 7;
+const EDGE_RECORD_LEN: usize = 5;
+const FLAG_TERMINAL: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DawgFormatError {
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u16),
+    UnsupportedAlphabet,
+    ChecksumMismatch { expected: u32, actual: u32 },
+    Malformed(&'static str),
+}
+
+impl std::fmt::Display for DawgFormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DawgFormatError::Truncated => write!(f, "dictionary blob is truncated"),
+            DawgFormatError::BadMagic => write!(f, "not a Lacuna dictionary blob"),
+            DawgFormatError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported dictionary format version {} (expected {})",
+                v, FORMAT_VERSION
+            ),
+            DawgFormatError::UnsupportedAlphabet => {
+                write!(f, "dictionary alphabet is not A-Z")
+            }
+            DawgFormatError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "dictionary checksum mismatch (header {:08x}, data {:08x})",
+                expected, actual
+            ),
+            DawgFormatError::Malformed(what) => write!(f, "malformed dictionary: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DawgFormatError {}
+
+impl Dawg {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut tables = Vec::with_capacity(
+            self.nodes.len() * NODE_RECORD_LEN + self.edges.len() * EDGE_RECORD_LEN,
+        );
+        for node in &self.nodes {
+            tables.extend_from_slice(&node.first_edge.to_le_bytes());
+            tables.push(node.edge_count);
+            tables.push(if node.is_terminal { FLAG_TERMINAL } else { 0 });
+            tables.push(node.score);
+        }
+        for edge in &self.edges {
+            tables.push(edge.label);
+            tables.extend_from_slice(&edge.target.to_le_bytes());
+        }
+
+        let mut out = Vec::with_capacity(tables.len() + 64);
+        out.extend_from_slice(&FORMAT_MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.push(ALPHABET.len() as u8);
+        out.extend_from_slice(ALPHABET);
+        out.extend_from_slice(&(self.word_count as u32).to_le_bytes());
+        out.extend_from_slice(&(self.nodes.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(self.edges.len() as u32).to_le_bytes());
+        out.extend_from_slice(&fnv1a(&tables).to_le_bytes());
+        out.extend_from_slice(&tables);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Dawg, DawgFormatError> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(4)? != FORMAT_MAGIC {
+            return Err(DawgFormatError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != FORMAT_VERSION {
+            return Err(DawgFormatError::UnsupportedVersion(version));
+        }
+        let alphabet_len = reader.u8()? as usize;
+        if reader.take(alphabet_len)? != ALPHABET {
+            return Err(DawgFormatError::UnsupportedAlphabet);
+        }
+        let word_count = reader.u32()? as usize;
+        let node_count = reader.u32()? as usize;
+        let edge_count = reader.u32()? as usize;
+        let expected = reader.u32()?;
+
+        let tables_len = node_count
+            .checked_mul(NODE_RECORD_LEN)
+            .and_then(|n| n.checked_add(edge_count.checked_mul(EDGE_RECORD_LEN)?))
+            .ok_or(DawgFormatError::Malformed("table sizes overflow"))?;
+        let tables = reader.take(tables_len)?;
+        if reader.pos != bytes.len() {
+            return Err(DawgFormatError::Malformed("trailing bytes after edge table"));
+        }
+        let actual = fnv1a(tables);
+        if actual != expected {
+            return Err(DawgFormatError::ChecksumMismatch { expected, actual });
+        }
+
+        let (node_bytes, edge_bytes) = tables.split_at(node_count * NODE_RECORD_LEN);
+        let nodes: Vec<DawgNode> = node_bytes
+            .chunks_exact(NODE_RECORD_LEN)
+            .map(|rec| DawgNode {
+                first_edge: u32::from_le_bytes([rec[0], rec[1], rec[2], rec[3]]),
+                edge_count: rec[4],
+                is_terminal: rec[5] & FLAG_TERMINAL != 0,
+                score: rec[6],
+            })
+            .collect();
+        let edges: Vec<DawgEdge> = edge_bytes
+            .chunks_exact(EDGE_RECORD_LEN)
+            .map(|rec| DawgEdge {
+                label: rec[0],
+                target: u32::from_le_bytes([rec[1], rec[2], rec[3], rec[4]]),
+            })
+            .collect();
+
+        let mut dawg = Dawg {
+            nodes,
+            edges,
+            word_count,
+            max_length: 0,
+        };
+        dawg.max_length = dawg.validate()?;
+        Ok(dawg)
+    }
+
+    // Structural checks so a blob that passes the checksum but was produced
+    // by a buggy writer can't send the solver out of bounds or into a cycle.
+    // Returns the length of the longest word.
+    fn validate(&self) -> Result<usize, DawgFormatError> {
+        if self.nodes.is_empty() {
+            return Err(DawgFormatError::Malformed("missing root node"));
+        }
+
+        // The builder freezes children before their parents, so every
+        // non-root node only points at lower indices. Checking that keeps the
+        // graph acyclic and lets us count words in one forward pass.
+        let mut words_below = vec![0usize; self.nodes.len()];
+        let mut longest_below = vec![0usize; self.nodes.len()];
+        for idx in (1..self.nodes.len()).chain(std::iter::once(0)) {
+            let node = &self.nodes[idx];
+            let start = node.first_edge as usize;
+            let end = start + node.edge_count as usize;
+            if end > self.edges.len() {
+                return Err(DawgFormatError::Malformed("edge range out of bounds"));
+            }
+
+            let mut count = node.is_terminal as usize;
+            let mut longest = 0;
+            let mut last_label = None;
+            for edge in &self.edges[start..end] {
+                if edge.label as usize >= ALPHABET.len() {
+                    return Err(DawgFormatError::Malformed("edge label outside alphabet"));
+                }
+                if last_label.is_some_and(|l| l >= edge.label) {
+                    return Err(DawgFormatError::Malformed("edges not sorted by label"));
+                }
+                last_label = Some(edge.label);
+
+                let target = edge.target as usize;
+                let limit = if idx == 0 { self.nodes.len() } else { idx };
+                if target == 0 || target >= limit {
+                    return Err(DawgFormatError::Malformed("edge target out of order"));
+                }
+                count = count.saturating_add(words_below[target]);
+                if words_below[target] > 0 {
+                    longest = longest.max(longest_below[target] + 1);
+                }
+            }
+            words_below[idx] = count;
+            longest_below[idx] = longest;
+        }
+
+        if words_below[0] != self.word_count {
+            return Err(DawgFormatError::Malformed("word count does not match graph"));
+        }
+        Ok(longest_below[0])
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DawgFormatError> {
+        let end = self.pos.checked_add(len).ok_or(DawgFormatError::Truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(DawgFormatError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, DawgFormatError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, DawgFormatError> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, DawgFormatError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811c_9dc5;
+    for &b in bytes {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+    // Random words over a small alphabet, so prefixes and suffixes are
+    // shared often.
+    fn random_words(rng: &mut StdRng, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                let len = rng.gen_range(1..=8);
+                (0..len).map(|_| (b'A' + rng.gen_range(0..4)) as char).collect()
+            })
+            .collect()
+    }
+
+    // No two nodes with the same terminal flag, score and edges, which is
+    // what minimal means for a DAWG whose children are already unique.
+    fn assert_minimal(dawg: &Dawg) {
+        let mut seen = HashSet::new();
+        for (idx, node) in dawg.nodes.iter().enumerate() {
+            let edges: Vec<(u8, u32)> = dawg.edges(idx).iter().map(|e| (e.label, e.target)).collect();
+            assert!(
+                seen.insert((node.is_terminal, node.score, edges)),
+                "node {} duplicates another",
+                idx
+            );
+        }
+    }
+
+    #[test]
+    fn holds_exactly_the_input_words() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for count in [0, 1, 10, 200, 2000] {
+            let words = random_words(&mut rng, count);
+            let dawg = Dawg::from_words(&words);
+            let expected: BTreeSet<String> = words.iter().cloned().collect();
+
+            assert_eq!(dawg.words(), expected.iter().cloned().collect::<Vec<_>>());
+            assert_eq!(dawg.word_count, expected.len());
+            assert_eq!(dawg.max_length, expected.iter().map(String::len).max().unwrap_or(0));
+            for word in &expected {
+                assert!(dawg.is_word(word));
+                assert!(dawg.is_word(&word.to_ascii_lowercase()));
+                for end in 1..word.len() {
+                    assert!(dawg.is_valid_prefix(&word[..end]));
+                    assert_eq!(dawg.is_word(&word[..end]), expected.contains(&word[..end]));
+                }
             }
+            assert!(!dawg.is_word("E"));
+            assert_minimal(&dawg);
         }
-        Some(self.nodes[node_idx].children.keys().cloned().collect())
+    }
+
+    #[test]
+    fn shares_suffixes() {
+        let words = ["BAKING", "MAKING", "TAKING", "WAKING"];
+        let dawg = Dawg::from_words(words);
+        // The root, then one chain all four first letters lead into: a
+        // node before each of A, K, I, N and G, and the final one
+        assert_eq!(dawg.nodes.len(), 1 + 5 + 1);
+        assert_eq!(dawg.words(), words);
+    }
+
+    #[test]
+    fn cleans_up_input() {
+        let dawg = Dawg::from_words(["cat", "CAT", "Dog", "", "it's", "x-ray", "EMU"]);
+        assert_eq!(dawg.words(), ["CAT", "DOG", "EMU"]);
+    }
+
+    #[test]
+    fn keeps_scores_apart() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut expected = BTreeMap::new();
+        for word in random_words(&mut rng, 500) {
+            expected.entry(word).or_insert_with(|| rng.gen_range(0..=100u8));
+        }
+        let dawg = Dawg::from_scored_words(expected.iter().map(|(w, &s)| (w.as_str(), s)));
+        assert_eq!(dawg.scored_words(), expected.iter().map(|(w, &s)| (w.clone(), s)).collect::<Vec<_>>());
+        for (word, &score) in &expected {
+            assert_eq!(dawg.score(word), Some(score));
+        }
+        assert_minimal(&dawg);
+    }
+
+    #[test]
+    fn reads_scored_entries() {
+        let dawg = Dawg::from_entries(["CAT;70", "DOG", "EMU;oops", "GNU;300"]);
+        assert_eq!(dawg.score("CAT"), Some(70));
+        assert_eq!(dawg.score("DOG"), Some(DEFAULT_SCORE));
+        assert_eq!(dawg.score("GNU"), Some(u8::MAX));
+        assert!(!dawg.is_word("EMU"));
+    }
+
+    #[test]
+    fn merges_like_a_fresh_build() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let scored = |rng: &mut StdRng, count| {
+            random_words(rng, count)
+                .into_iter()
+                .map(|w| (w, rng.gen_range(0..=100u8)))
+                .collect::<Vec<_>>()
+        };
+        let (a, b) = (scored(&mut rng, 300), scored(&mut rng, 300));
+        let all = Dawg::from_scored_words(a.iter().chain(&b).map(|(w, s)| (w.as_str(), *s)));
+
+        let merged = Dawg::from_scored_words(a.clone()).merged(&Dawg::from_scored_words(b.clone()));
+        let added = Dawg::from_scored_words(a).with_scored_words(b);
+        for dawg in [merged, added] {
+            assert_eq!(dawg.scored_words(), all.scored_words());
+            assert_eq!(dawg.nodes, all.nodes);
+            assert_eq!(dawg.edges, all.edges);
+            assert_eq!(dawg.max_length, all.max_length);
+        }
+
+        let removed: Vec<String> = all.words().into_iter().step_by(3).collect();
+        let kept = all.without_words(removed.iter().map(|w| w.to_ascii_lowercase()));
+        assert_eq!(kept.word_count, all.word_count - removed.len());
+        assert!(removed.iter().all(|w| !kept.is_word(w)));
+        assert_minimal(&kept);
+    }
+
+    // Where the tables start: magic, version, alphabet, then four u32s
+    const HEADER_LEN: usize = 4 + 2 + 1 + 26 + 4 * 4;
+    const CHECKSUM_AT: usize = HEADER_LEN - 4;
+
+    fn sample() -> Dawg {
+        Dawg::from_entries(["CAT;70", "CATS", "DOG;20", "DOGS;255", "EMU", "ZEBRA;0"])
+    }
+
+    // Rewrites the checksum after the tables were tampered with, so only
+    // the structural checks stand in the way.
+    fn reseal(bytes: &mut [u8]) {
+        let checksum = fnv1a(&bytes[HEADER_LEN..]);
+        bytes[CHECKSUM_AT..HEADER_LEN].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for dawg in [Dawg::new(), sample(), Dawg::from_words(random_words(&mut rng, 3000))] {
+            let bytes = dawg.to_bytes();
+            assert_eq!(&bytes[..4], b"LDWG");
+            assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), 2);
+
+            let loaded = Dawg::from_bytes(&bytes).unwrap();
+            assert_eq!(loaded.nodes, dawg.nodes);
+            assert_eq!(loaded.edges, dawg.edges);
+            assert_eq!(loaded.word_count, dawg.word_count);
+            assert_eq!(loaded.max_length, dawg.max_length);
+            assert_eq!(loaded.scored_words(), dawg.scored_words());
+            assert_eq!(loaded.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_blobs() {
+        let bytes = sample().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Dawg::from_bytes(&bytes[..len]).is_err(), "accepted {} of {} bytes", len, bytes.len());
+        }
+        assert_eq!(Dawg::from_bytes(&bytes[..HEADER_LEN]).err(), Some(DawgFormatError::Truncated));
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let mut bytes = sample().to_bytes();
+        bytes.push(0);
+        assert!(matches!(Dawg::from_bytes(&bytes), Err(DawgFormatError::Malformed(_))));
+    }
+
+    #[test]
+    fn rejects_other_formats() {
+        let bytes = sample().to_bytes();
+
+        let mut magic = bytes.clone();
+        magic[..4].copy_from_slice(b"DAWG");
+        assert_eq!(Dawg::from_bytes(&magic).err(), Some(DawgFormatError::BadMagic));
+
+        for version in [0u16, 1, 3, u16::MAX] {
+            let mut other = bytes.clone();
+            other[4..6].copy_from_slice(&version.to_le_bytes());
+            assert_eq!(Dawg::from_bytes(&other).err(), Some(DawgFormatError::UnsupportedVersion(version)));
+        }
+
+        let mut alphabet = bytes.clone();
+        alphabet[7] = b'a';
+        assert_eq!(Dawg::from_bytes(&alphabet).err(), Some(DawgFormatError::UnsupportedAlphabet));
+    }
+
+    #[test]
+    fn rejects_corrupted_tables() {
+        let bytes = sample().to_bytes();
+        for at in HEADER_LEN..bytes.len() {
+            let mut corrupted = bytes.clone();
+            corrupted[at] ^= 0x40;
+            assert!(
+                matches!(Dawg::from_bytes(&corrupted), Err(DawgFormatError::ChecksumMismatch { .. })),
+                "accepted a flipped byte at {}",
+                at
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_bad_structure_with_a_good_checksum() {
+        let dawg = sample();
+        let edges_at = HEADER_LEN + dawg.nodes.len() * NODE_RECORD_LEN;
+        let bytes = dawg.to_bytes();
+
+        // An edge pointing back at the root would make a cycle
+        let mut cycle = bytes.clone();
+        cycle[edges_at + 1..edges_at + 5].copy_from_slice(&0u32.to_le_bytes());
+        reseal(&mut cycle);
+        assert!(matches!(Dawg::from_bytes(&cycle), Err(DawgFormatError::Malformed(_))));
+
+        let mut label = bytes.clone();
+        label[edges_at] = 26;
+        reseal(&mut label);
+        assert!(matches!(Dawg::from_bytes(&label), Err(DawgFormatError::Malformed(_))));
+
+        // One word more than the graph holds
+        let mut count = bytes.clone();
+        let word_count_at = 4 + 2 + 1 + 26;
+        count[word_count_at..word_count_at + 4].copy_from_slice(&(dawg.word_count as u32 + 1).to_le_bytes());
+        assert!(matches!(Dawg::from_bytes(&count), Err(DawgFormatError::Malformed(_))));
     }
 }
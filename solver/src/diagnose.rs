@@ -0,0 +1,318 @@
+use crate::domain::Domain;
+use crate::solver::{SearchStatus, Slot, Solver, SolverStats};
+use serde::Serialize;
+
+// A slot the client can highlight, with the letters it was given.
+#[derive(Clone, Debug, Serialize)]
+pub struct SlotRef {
+    pub id: usize,
+    pub r: usize,
+    pub c: usize,
+    pub length: usize,
+    pub is_across: bool,
+    pub pattern: String, // Fixed letters, '?' for open cells
+}
+
+// A cell where the across and down slots each have words on their own,
+// but no letter that works for both.
+#[derive(Clone, Debug, Serialize)]
+pub struct Crossing {
+    pub r: usize,
+    pub c: usize,
+    pub across: usize,
+    pub down: usize,
+    pub across_letters: String,
+    pub down_letters: String,
+}
+
+// Slots and fixed letters that can't all hold together. Dropping any one
+// of them makes the rest fillable, unless `minimal` is false because a
+// budget ran out while checking.
+#[derive(Clone, Debug, Serialize)]
+pub struct Conflict {
+    pub slots: Vec<SlotRef>,
+    pub fixed_cells: Vec<(usize, usize, char)>,
+    pub minimal: bool,
+}
+
+pub struct Diagnosis {
+    // Solved if the grid has a fill after all
+    pub status: SearchStatus,
+    pub empty_slots: Vec<SlotRef>,
+    pub crossings: Vec<Crossing>,
+    pub conflict: Option<Conflict>,
+    pub stats: SolverStats,
+}
+
+// Explains why `base`'s grid has no fill.
+//
+// The grid is first propagated, and each slot is checked alone against the
+// letters that leaves its cells, which finds slots no word fits and
+// crossings whose two slots share no letter. Propagation stops at the first
+// slot it empties, but every letter it took out by then was one no word
+// could use, so what it reports still holds. Then the whole grid is solved,
+// and if it has no fill, slots and fixed letters are dropped one at a time,
+// keeping each drop that leaves the grid still unfillable. What is left is
+// a conflict set where every member matters. The checks carry on one
+// another's counts, so `base`'s budgets bound the whole diagnosis.
+pub fn diagnose(base: &Solver) -> Diagnosis {
+    let slots = base.slots();
+    let mut stats = base.stats().clone();
+    let mut propagated = base.clone();
+    propagated.continue_stats(std::mem::take(&mut stats));
+    propagated.propagate();
+    stats = propagated.take_stats();
+    let letters: Vec<Option<Vec<Domain>>> = (0..slots.len()).map(|i| propagated.slot_letters(i)).collect();
+
+    let empty_slots: Vec<SlotRef> = slots
+        .iter()
+        .filter(|slot| letters[slot.id].is_none())
+        .map(|slot| slot_ref(base, slot))
+        .collect();
+
+    let mut crossings: Vec<Crossing> = Vec::new();
+    for across in slots.iter().filter(|slot| slot.is_across) {
+        let Some(across_letters) = &letters[across.id] else {
+            continue;
+        };
+        for down in slots.iter().filter(|slot| !slot.is_across) {
+            let Some(down_letters) = &letters[down.id] else {
+                continue;
+            };
+            let (r, c) = (across.r, down.c);
+            if !across.cells.contains(&(r, c)) || !down.cells.contains(&(r, c)) {
+                continue;
+            }
+            let across_here = across_letters[c - across.c];
+            let down_here = down_letters[r - down.r];
+            if across_here.intersect(down_here).is_empty() {
+                crossings.push(Crossing {
+                    r,
+                    c,
+                    across: across.id,
+                    down: down.id,
+                    across_letters: across_here.iter().collect(),
+                    down_letters: down_here.iter().collect(),
+                });
+            }
+        }
+    }
+
+    let status = relaxed(base, &[], &[], &mut stats);
+    if status != SearchStatus::Unsatisfiable {
+        return Diagnosis {
+            status,
+            empty_slots,
+            crossings,
+            conflict: None,
+            stats,
+        };
+    }
+
+    let fixed: Vec<(usize, usize, char)> = (0..base.grid().height)
+        .flat_map(|r| (0..base.grid().width).map(move |c| (r, c)))
+        .filter_map(|(r, c)| base.grid().get_cell(r, c).fixed_char.map(|letter| (r, c, letter)))
+        .collect();
+    // Slots already found at fault are tried last, so the conflict set
+    // ends up around them if it can.
+    let mut order: Vec<usize> = (0..slots.len()).collect();
+    order.sort_by_key(|&slot| {
+        empty_slots.iter().any(|empty| empty.id == slot)
+            || crossings.iter().any(|crossing| crossing.across == slot || crossing.down == slot)
+    });
+    let mut dropped_slots = Vec::new();
+    let mut dropped_cells = Vec::new();
+    let mut minimal = true;
+    for slot in order {
+        dropped_slots.push(slot);
+        match relaxed(base, &dropped_slots, &dropped_cells, &mut stats) {
+            SearchStatus::Unsatisfiable => {}
+            status => {
+                dropped_slots.pop();
+                minimal &= status == SearchStatus::Solved;
+            }
+        }
+    }
+    for &(r, c, _) in &fixed {
+        dropped_cells.push((r, c));
+        match relaxed(base, &dropped_slots, &dropped_cells, &mut stats) {
+            SearchStatus::Unsatisfiable => {}
+            status => {
+                dropped_cells.pop();
+                minimal &= status == SearchStatus::Solved;
+            }
+        }
+    }
+
+    let conflict = Conflict {
+        slots: slots
+            .iter()
+            .filter(|slot| !dropped_slots.contains(&slot.id))
+            .map(|slot| slot_ref(base, slot))
+            .collect(),
+        fixed_cells: fixed
+            .into_iter()
+            .filter(|&(r, c, _)| !dropped_cells.contains(&(r, c)))
+            .collect(),
+        minimal,
+    };
+    Diagnosis {
+        status,
+        empty_slots,
+        crossings,
+        conflict: Some(conflict),
+        stats,
+    }
+}
+
+// Solves `base` without the given slots and fixed letters.
+fn relaxed(base: &Solver, slots: &[usize], cells: &[(usize, usize)], stats: &mut SolverStats) -> SearchStatus {
+    let mut solver = base.clone();
+    solver.continue_stats(std::mem::take(stats));
+    for &slot in slots {
+        solver.ignore_slot(slot);
+    }
+    for &(r, c) in cells {
+        solver.unfix_cell(r, c);
+    }
+    let status = solver.step(usize::MAX);
+    *stats = solver.take_stats();
+    status
+}
+
+fn slot_ref(solver: &Solver, slot: &Slot) -> SlotRef {
+    SlotRef {
+        id: slot.id,
+        r: slot.r,
+        c: slot.c,
+        length: slot.length,
+        is_across: slot.is_across,
+        pattern: slot
+            .cells
+            .iter()
+            .map(|&(r, c)| solver.grid().get_cell(r, c).fixed_char.unwrap_or('?'))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dawg::Dawg;
+    use crate::grid::Grid;
+    use crate::solver::SolveOptions;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use std::rc::Rc;
+
+    // Whether some letters A-C in the white cells keep the given fixed
+    // letters and spell a word in each of the given slots.
+    fn fillable(solver: &Solver, words: &[String], slots: &[usize], fixed: &[(usize, usize, char)]) -> bool {
+        let grid = solver.grid();
+        let white: Vec<usize> = (0..grid.cells.len()).filter(|&i| !grid.cells[i].is_black).collect();
+        let mut letters = vec!['#'; grid.cells.len()];
+        (0..3usize.pow(white.len() as u32)).any(|n| {
+            let mut rest = n;
+            for &i in &white {
+                letters[i] = (b'A' + (rest % 3) as u8) as char;
+                rest /= 3;
+            }
+            fixed.iter().all(|&(r, c, letter)| letters[grid.get_index(r, c)] == letter)
+                && slots.iter().all(|&slot| {
+                    let word: String = solver.slots()[slot].cells.iter().map(|&(r, c)| letters[grid.get_index(r, c)]).collect();
+                    words.contains(&word)
+                })
+        })
+    }
+
+    fn random_solver(rng: &mut ChaCha8Rng) -> (Solver, Vec<String>) {
+        let mut grid = Grid::new(3, 3);
+        for _ in 0..rng.gen_range(0..3) {
+            grid.set_black(rng.gen_range(0..3), rng.gen_range(0..3), true);
+        }
+        for _ in 0..rng.gen_range(0..3) {
+            let (r, c) = (rng.gen_range(0..3), rng.gen_range(0..3));
+            let letter = (b'A' + rng.gen_range(0..3)) as char;
+            let cell = grid.get_cell_mut(r, c);
+            if !cell.is_black {
+                cell.domain = Domain::from_char(letter);
+                cell.fixed_char = Some(letter);
+            }
+        }
+        let mut words: Vec<String> = (0..rng.gen_range(4..10))
+            .map(|_| (0..rng.gen_range(2..4)).map(|_| (b'A' + rng.gen_range(0..3)) as char).collect())
+            .collect();
+        words.sort();
+        words.dedup();
+        let options = SolveOptions {
+            allow_duplicates: true,
+            ..SolveOptions::default()
+        };
+        (Solver::with_options(grid, Rc::new(Dawg::from_words(&words)), options), words)
+    }
+
+    #[test]
+    fn conflicts_are_minimal() {
+        let mut rng = ChaCha8Rng::seed_from_u64(16);
+        let mut conflicts = 0;
+        for _ in 0..60 {
+            let (solver, words) = random_solver(&mut rng);
+            let all_slots: Vec<usize> = (0..solver.slots().len()).collect();
+            let all_fixed: Vec<(usize, usize, char)> = (0..3)
+                .flat_map(|r| (0..3).map(move |c| (r, c)))
+                .filter_map(|(r, c)| solver.grid().get_cell(r, c).fixed_char.map(|letter| (r, c, letter)))
+                .collect();
+            let diagnosis = diagnose(&solver);
+            if fillable(&solver, &words, &all_slots, &all_fixed) {
+                assert_eq!(diagnosis.status, SearchStatus::Solved);
+                assert!(diagnosis.conflict.is_none());
+                continue;
+            }
+            assert_eq!(diagnosis.status, SearchStatus::Unsatisfiable);
+            let conflict = diagnosis.conflict.unwrap();
+            assert!(conflict.minimal);
+            let slots: Vec<usize> = conflict.slots.iter().map(|slot| slot.id).collect();
+            let fixed = conflict.fixed_cells;
+            assert!(!fillable(&solver, &words, &slots, &fixed));
+            // Dropping any one member lets the rest be filled
+            for i in 0..slots.len() {
+                let mut fewer = slots.clone();
+                fewer.remove(i);
+                assert!(fillable(&solver, &words, &fewer, &fixed));
+            }
+            for i in 0..fixed.len() {
+                let mut fewer = fixed.clone();
+                fewer.remove(i);
+                assert!(fillable(&solver, &words, &slots, &fewer));
+            }
+            conflicts += 1;
+        }
+        assert!(conflicts > 10);
+    }
+
+    #[test]
+    fn empty_slots_come_from_the_propagated_grid() {
+        // Every slot has a word on its own, but the down words start each
+        // row with a letter no across word starts with.
+        let solver = Solver::new(Grid::new(2, 2), Rc::new(Dawg::from_words(["AB", "CD"])));
+        assert!((0..solver.slots().len()).all(|i| solver.slot_letters(i).is_some()));
+        let diagnosis = diagnose(&solver);
+        assert_eq!(diagnosis.status, SearchStatus::Unsatisfiable);
+        assert!(!diagnosis.empty_slots.is_empty() || !diagnosis.crossings.is_empty());
+    }
+
+    #[test]
+    fn budgets_cover_the_whole_diagnosis() {
+        let mut rng = ChaCha8Rng::seed_from_u64(16);
+        for _ in 0..60 {
+            let (solver, words) = random_solver(&mut rng);
+            let options = SolveOptions {
+                allow_duplicates: true,
+                max_nodes: Some(3),
+                ..SolveOptions::default()
+            };
+            let bounded = Solver::with_options(solver.grid().clone(), Rc::new(Dawg::from_words(&words)), options);
+            assert!(diagnose(&bounded).stats.nodes <= 3);
+        }
+    }
+}
@@ -13,7 +13,7 @@ impl Domain {
 
     pub fn from_char(c: char) -> Self {
         let c = c.to_ascii_uppercase();
-        if c >= 'A' && c <= 'Z' {
+        if c.is_ascii_uppercase() {
             Domain(1 << (c as u8 - b'A'))
         } else {
             Domain::empty()
@@ -22,7 +22,7 @@ impl Domain {
 
     pub fn contains(&self, c: char) -> bool {
         let c = c.to_ascii_uppercase();
-        if c >= 'A' && c <= 'Z' {
+        if c.is_ascii_uppercase() {
             (self.0 & (1 << (c as u8 - b'A'))) != 0
         } else {
             false
@@ -39,7 +39,7 @@ impl Domain {
 
     pub fn remove(&mut self, c: char) {
         let c = c.to_ascii_uppercase();
-        if c >= 'A' && c <= 'Z' {
+        if c.is_ascii_uppercase() {
             self.0 &= !(1 << (c as u8 - b'A'));
         }
     }
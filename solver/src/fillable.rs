@@ -0,0 +1,181 @@
+use crate::grid::Grid;
+use crate::layout::{LayoutError, LayoutGenerator, LayoutOptions, Rejections};
+use crate::solver::{Limit, SlotEntry, SolveOptions, SolveOutcome, Solver, SolverStats};
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+
+// Settings for generating a layout together with a fill for it. The
+// layout settings sit at the top level, as for a plain layout.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FillableOptions {
+    #[serde(flatten)]
+    pub layout: LayoutOptions,
+    // Budgets here bound each fill attempt. The layout's `time_limit_ms`
+    // covers the whole run, fill attempts included, and cuts an attempt
+    // short when it runs out.
+    pub solve: SolveOptions,
+    pub max_layouts: NonZeroUsize, // Layouts handed to the solver before giving up
+}
+
+impl Default for FillableOptions {
+    fn default() -> Self {
+        FillableOptions {
+            layout: LayoutOptions::default(),
+            solve: SolveOptions {
+                max_backtracks: Some(1000),
+                ..SolveOptions::default()
+            },
+            max_layouts: NonZeroUsize::new(20).unwrap(),
+        }
+    }
+}
+
+pub struct FilledLayout {
+    pub layout: Grid, // Black squares only
+    pub solution: Grid,
+    pub entries: Vec<SlotEntry>,
+}
+
+pub struct FillableRun {
+    pub result: Result<FilledLayout, LayoutError>,
+    pub rejections: Rejections,
+    pub stats: SolverStats, // Over every fill attempt
+}
+
+// Generates layouts until one can be filled. `generator` should carry the
+// dictionary, so it already steers clear of squares the words can't work
+// around; each layout it comes up with then goes to a solver from
+// `solver_for`, with `solve` as its options, and is thrown away if that
+// finds no fill.
+pub fn generate_fillable(
+    generator: &LayoutGenerator,
+    max_layouts: NonZeroUsize,
+    solve: &SolveOptions,
+    solver_for: impl Fn(Grid, SolveOptions) -> Solver,
+) -> FillableRun {
+    let mut layouts = match generator.layouts() {
+        Ok(layouts) => layouts,
+        Err(e) => {
+            return FillableRun {
+                result: Err(e),
+                rejections: Rejections::default(),
+                stats: SolverStats::default(),
+            }
+        }
+    };
+
+    let mut stats: Option<SolverStats> = None;
+    let mut tried = 0;
+    let result = loop {
+        if tried == max_layouts.get() {
+            break Err(LayoutError::Unfillable { layouts: tried });
+        }
+        let layout = match layouts.next() {
+            Some(Ok(layout)) => layout,
+            Some(Err(e)) => break Err(e),
+            None => break Err(LayoutError::Unfillable { layouts: tried }),
+        };
+        tried += 1;
+
+        // The attempt gets no longer than the run has left
+        let mut options = solve.clone();
+        if let Some(left) = layouts.time_left() {
+            let left = left.as_millis() as u64;
+            options.time_limit_ms = Some(options.time_limit_ms.map_or(left, |own| own.min(left)));
+        }
+        let mut solver = solver_for(layout.clone(), options);
+        let outcome = solver.solve();
+        let run = solver.take_stats();
+        match &mut stats {
+            Some(stats) => stats.merge(&run),
+            None => stats = Some(run),
+        }
+        match outcome {
+            SolveOutcome::Solved(solution) => {
+                break Ok(FilledLayout {
+                    entries: solver.slot_entries(&solution),
+                    layout,
+                    solution,
+                });
+            }
+            SolveOutcome::Unsatisfiable => layouts.rejections.unfillable += 1,
+            SolveOutcome::GaveUp {
+                reason: Limit::Cancelled,
+                ..
+            } => break Err(LayoutError::Cancelled { tries: layouts.tries() }),
+            SolveOutcome::GaveUp { .. } => {
+                layouts.rejections.gave_up += 1;
+                if layouts.time_left().is_some_and(|left| left.is_zero()) {
+                    break Err(LayoutError::TimedOut { tries: layouts.tries() });
+                }
+            }
+        }
+    };
+
+    FillableRun {
+        result,
+        rejections: layouts.rejections,
+        stats: stats.unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dawg::Dawg;
+    use std::rc::Rc;
+
+    fn generator(words: &[impl AsRef<str>]) -> LayoutGenerator {
+        let options = LayoutOptions {
+            max_tries: 20,
+            time_limit_ms: None,
+            ..LayoutOptions::default()
+        };
+        LayoutGenerator::new(5, 5)
+            .with_options(options)
+            .with_seed(25)
+            .with_dictionary(Rc::new(Dawg::from_words(words)))
+    }
+
+    // Every word of two to five letters over A-B, so any layout propagates
+    fn words() -> Vec<String> {
+        (2..=5)
+            .flat_map(|length| (0..1 << length).map(move |n: usize| (0..length).map(|i| if n >> i & 1 == 1 { 'B' } else { 'A' }).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn cancelling_stops_generation_and_fill_attempts() {
+        let layouts = NonZeroUsize::new(5).unwrap();
+        let dawg = Rc::new(Dawg::from_words(words()));
+        let cancelled = generator(&words()).with_cancel_check(Rc::new(|| true));
+        let run = generate_fillable(&cancelled, layouts, &SolveOptions::default(), |grid, options| {
+            Solver::with_options(grid, dawg.clone(), options)
+        });
+        assert_eq!(run.result.err(), Some(LayoutError::Cancelled { tries: 0 }));
+
+        // A fill attempt cancelled partway ends the run rather than moving
+        // on to the next layout
+        let run = generate_fillable(&generator(&words()), layouts, &SolveOptions::default(), |grid, options| {
+            let mut solver = Solver::with_options(grid, dawg.clone(), options);
+            solver.set_cancel_check(Rc::new(|| true));
+            solver
+        });
+        assert!(matches!(run.result, Err(LayoutError::Cancelled { .. })));
+        assert_eq!(run.rejections.gave_up, 0);
+    }
+
+    #[test]
+    fn tries_that_never_propagate_are_not_dead_ends() {
+        // One word of each length, so crossing entries can't agree
+        let words = ["AB", "ABC", "ABCD", "ABCDE"];
+        let generator = generator(&words);
+        let mut layouts = generator.layouts().unwrap();
+        assert!(matches!(layouts.by_ref().last(), Some(Err(LayoutError::NoLayout { tries: 20 }))));
+        let rejections = layouts.rejections;
+        assert!(rejections.never_propagated > 0);
+        assert_eq!(rejections.dead_ends, 0);
+        assert_eq!(rejections.broke_rules + rejections.missing_lengths + rejections.never_propagated, 20);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::dawg::Dawg;
+use crate::pattern::{Collector, PatternError, WordMatches, WordOrder};
+use serde::Deserialize;
+
+// A lookup of the dictionary words near a given one, for spellchecking
+// theme words and suggesting near misses.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct FuzzyQuery {
+    pub word: String,
+    pub max_distance: usize,
+    pub length: Option<usize>, // Only words this long, e.g. to fit a slot
+    pub min_score: u8,
+    pub order: WordOrder,
+    pub limit: Option<usize>,
+}
+
+impl Default for FuzzyQuery {
+    fn default() -> Self {
+        FuzzyQuery {
+            word: String::new(),
+            max_distance: 2,
+            length: None,
+            min_score: 0,
+            order: WordOrder::Distance,
+            limit: None,
+        }
+    }
+}
+
+pub fn find_similar(dawg: &Dawg, query: &FuzzyQuery) -> Result<WordMatches, PatternError> {
+    let mut target = Vec::with_capacity(query.word.len());
+    for (at, ch) in query.word.chars().enumerate() {
+        if !ch.is_ascii_alphabetic() {
+            return Err(PatternError::UnexpectedChar { at, ch });
+        }
+        target.push(ch.to_ascii_uppercase() as u8 - b'A');
+    }
+    let lengths = match query.length {
+        Some(length) => length..=length,
+        None => 1..=usize::MAX,
+    };
+    let mut found = Collector::new(query.min_score, query.order, query.limit);
+    dawg.walk_similar(&target, query.max_distance, lengths, &mut |word, score, distance| {
+        found.visit_near(word, score, distance)
+    });
+    Ok(found.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+    use std::cmp::Reverse;
+
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for j in 0..b.len() {
+                let next = (diagonal + (ca != b[j]) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+                diagonal = row[j + 1];
+                row[j + 1] = next;
+            }
+        }
+        row[b.len()]
+    }
+
+    // Words of one to six letters over A-D, with random scores.
+    fn scored_words(seed: u64) -> Vec<(String, u8)> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut words: Vec<(String, u8)> = (0..400)
+            .map(|_| {
+                let word = (0..rng.gen_range(1..=6)).map(|_| (b'A' + rng.gen_range(0..4)) as char).collect();
+                (word, rng.gen())
+            })
+            .collect();
+        words.sort();
+        words.dedup_by(|a, b| a.0 == b.0);
+        words
+    }
+
+    #[test]
+    fn finds_the_words_within_the_distance() {
+        let words = scored_words(20);
+        let dawg = Dawg::from_scored_words(words.iter().map(|(w, score)| (w, *score)));
+        let mut total = 0;
+        for word in ["ABCD", "dab", "A", "", "CCCCCC", "ABZ"] {
+            for max_distance in 0..=2 {
+                for length in [None, Some(3)] {
+                    let upper = word.to_ascii_uppercase();
+                    // Closest first, ties best first, then alphabetical
+                    let mut expected: Vec<(String, u8, usize)> = words
+                        .iter()
+                        .map(|(w, score)| (w.clone(), *score, levenshtein(&upper, w)))
+                        .filter(|(w, _, distance)| *distance <= max_distance && length.is_none_or(|n| w.len() == n))
+                        .collect();
+                    expected.sort_by_key(|(_, score, distance)| (*distance, Reverse(*score)));
+                    let query = FuzzyQuery {
+                        word: word.to_string(),
+                        max_distance,
+                        length,
+                        ..FuzzyQuery::default()
+                    };
+                    let found: Vec<(String, u8, usize)> = find_similar(&dawg, &query)
+                        .unwrap()
+                        .words
+                        .into_iter()
+                        .map(|w| (w.word, w.score, w.distance.unwrap()))
+                        .collect();
+                    assert_eq!(found, expected, "{} {} {:?}", word, max_distance, length);
+                    total += expected.len();
+                }
+            }
+        }
+        assert!(total > 100);
+    }
+
+    #[test]
+    fn near_misses_can_come_alphabetically_or_by_score() {
+        let words = scored_words(20);
+        let dawg = Dawg::from_scored_words(words.iter().map(|(w, score)| (w, *score)));
+        let near: Vec<&(String, u8)> = words.iter().filter(|(w, _)| levenshtein("BAD", w) <= 1).collect();
+        let query = |order| FuzzyQuery {
+            word: "BAD".to_string(),
+            max_distance: 1,
+            order,
+            ..FuzzyQuery::default()
+        };
+        let found = |order| -> Vec<String> {
+            find_similar(&dawg, &query(order)).unwrap().words.into_iter().map(|w| w.word).collect()
+        };
+        assert_eq!(found(WordOrder::Alphabetical), near.iter().map(|(w, _)| w.clone()).collect::<Vec<_>>());
+        let mut best = near.clone();
+        best.sort_by_key(|(w, score)| (Reverse(*score), levenshtein("BAD", w)));
+        assert_eq!(found(WordOrder::Score), best.iter().map(|(w, _)| w.clone()).collect::<Vec<_>>());
+        assert!(near.len() > 3);
+        assert_eq!(
+            find_similar(&dawg, &FuzzyQuery { word: "B-D".to_string(), ..query(WordOrder::Distance) }).err(),
+            Some(PatternError::UnexpectedChar { at: 1, ch: '-' })
+        );
+    }
+}
@@ -58,4 +58,105 @@ pub struct GridConfig {
     pub height: usize,
     pub black_cells: Vec<(usize, usize)>,
     pub fixed_cells: Vec<(usize, usize, char)>,
+    // Seed a generated layout came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+// Widest or tallest grid a config may ask for, so a bad request can't make
+// the solver allocate and walk millions of cells.
+pub const MAX_SIDE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    TooLarge { width: usize, height: usize },
+    OutOfBounds { r: usize, c: usize },
+    InvalidFixedLetter { r: usize, c: usize, letter: char },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::TooLarge { width, height } => {
+                write!(f, "a {}x{} grid is larger than {} squares a side", width, height, MAX_SIDE)
+            }
+            ConfigError::OutOfBounds { r, c } => {
+                write!(f, "cell ({}, {}) is outside the grid", r, c)
+            }
+            ConfigError::InvalidFixedLetter { r, c, letter } => {
+                write!(f, "fixed letter {:?} at ({}, {}) is not a letter on a white cell", letter, r, c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Grid {
+    // Builds the grid a config describes, rejecting sizes over MAX_SIDE,
+    // coordinates outside the grid and fixed letters that aren't A-Z or sit
+    // on a black cell.
+    pub fn from_config(config: &GridConfig) -> Result<Grid, ConfigError> {
+        if config.width > MAX_SIDE || config.height > MAX_SIDE {
+            return Err(ConfigError::TooLarge {
+                width: config.width,
+                height: config.height,
+            });
+        }
+        let mut grid = Grid::new(config.width, config.height);
+        for &(r, c) in &config.black_cells {
+            if r >= grid.height || c >= grid.width {
+                return Err(ConfigError::OutOfBounds { r, c });
+            }
+            grid.set_black(r, c, true);
+        }
+        // Handle fixed cells if any (pre-filled)
+        for &(r, c, letter) in &config.fixed_cells {
+            if r >= grid.height || c >= grid.width {
+                return Err(ConfigError::OutOfBounds { r, c });
+            }
+            let cell = grid.get_cell_mut(r, c);
+            if cell.is_black || !letter.is_ascii_alphabetic() {
+                return Err(ConfigError::InvalidFixedLetter { r, c, letter });
+            }
+            cell.domain = Domain::from_char(letter);
+            cell.fixed_char = Some(letter.to_ascii_uppercase());
+        }
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(width: usize, height: usize, black_cells: &[(usize, usize)], fixed_cells: &[(usize, usize, char)]) -> GridConfig {
+        GridConfig {
+            width,
+            height,
+            black_cells: black_cells.to_vec(),
+            fixed_cells: fixed_cells.to_vec(),
+            seed: None,
+        }
+    }
+
+    #[test]
+    fn builds_the_configured_grid() {
+        let grid = Grid::from_config(&config(3, 2, &[(1, 2)], &[(0, 0, 'q')])).unwrap();
+        assert_eq!((grid.width, grid.height), (3, 2));
+        assert!(grid.get_cell(1, 2).is_black);
+        assert_eq!(grid.get_cell(0, 0).fixed_char, Some('Q'));
+        assert!(Grid::from_config(&config(MAX_SIDE, MAX_SIDE, &[], &[])).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_configs() {
+        let error = |config: GridConfig| Grid::from_config(&config).err();
+        assert_eq!(error(config(MAX_SIDE + 1, 5, &[], &[])), Some(ConfigError::TooLarge { width: MAX_SIDE + 1, height: 5 }));
+        assert_eq!(error(config(5, usize::MAX, &[], &[])), Some(ConfigError::TooLarge { width: 5, height: usize::MAX }));
+        assert_eq!(error(config(3, 3, &[(3, 0)], &[])), Some(ConfigError::OutOfBounds { r: 3, c: 0 }));
+        assert_eq!(error(config(3, 3, &[], &[(0, 3, 'A')])), Some(ConfigError::OutOfBounds { r: 0, c: 3 }));
+        assert_eq!(error(config(3, 3, &[], &[(1, 1, '7')])), Some(ConfigError::InvalidFixedLetter { r: 1, c: 1, letter: '7' }));
+        assert_eq!(error(config(3, 3, &[(1, 1)], &[(1, 1, 'A')])), Some(ConfigError::InvalidFixedLetter { r: 1, c: 1, letter: 'A' }));
+    }
 }
@@ -1,153 +1,584 @@
+use crate::dawg::Dawg;
 use crate::grid::Grid;
-use rand::Rng;
-use std::collections::VecDeque;
+use crate::rules::{runs, LayoutRules, Rule, Violation};
+use crate::solver::{SolveOptions, Solver};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::time::Duration;
+use web_time::Instant;
+
+// How black squares mirror each other across the grid.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symmetry {
+    #[default]
+    #[serde(rename = "rotational_180")]
+    Rotational180, // The usual one for American-style grids
+    #[serde(rename = "rotational_90")]
+    Rotational90, // Square grids only
+    LeftRight,
+    TopBottom,
+    Diagonal, // About the main diagonal; square grids only
+    None,
+}
+
+impl Symmetry {
+    // Every mode that actually mirrors something.
+    pub const MIRRORED: [Symmetry; 5] = [
+        Symmetry::Rotational180,
+        Symmetry::Rotational90,
+        Symmetry::LeftRight,
+        Symmetry::TopBottom,
+        Symmetry::Diagonal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::Rotational180 => "rotational_180",
+            Symmetry::Rotational90 => "rotational_90",
+            Symmetry::LeftRight => "left_right",
+            Symmetry::TopBottom => "top_bottom",
+            Symmetry::Diagonal => "diagonal",
+            Symmetry::None => "none",
+        }
+    }
+
+    // Whether the symmetry maps a grid of this size onto itself.
+    pub fn fits(self, width: usize, height: usize) -> bool {
+        width == height || !matches!(self, Symmetry::Rotational90 | Symmetry::Diagonal)
+    }
+
+    // The cells that must match `(r, c)`, itself included. Only for sizes
+    // the symmetry fits.
+    pub fn orbit(self, r: usize, c: usize, width: usize, height: usize) -> Vec<(usize, usize)> {
+        let mut cells = vec![(r, c)];
+        match self {
+            Symmetry::Rotational180 => cells.push((height - 1 - r, width - 1 - c)),
+            Symmetry::Rotational90 => {
+                let n = width;
+                cells.push((c, n - 1 - r));
+                cells.push((n - 1 - r, n - 1 - c));
+                cells.push((n - 1 - c, r));
+            }
+            Symmetry::LeftRight => cells.push((r, width - 1 - c)),
+            Symmetry::TopBottom => cells.push((height - 1 - r, c)),
+            Symmetry::Diagonal => cells.push((c, r)),
+            Symmetry::None => {}
+        }
+        cells.sort_unstable();
+        cells.dedup();
+        cells
+    }
+
+    // Whether the grid's black squares already mirror each other this way.
+    pub fn holds(self, grid: &Grid) -> bool {
+        let (w, h) = (grid.width, grid.height);
+        self.fits(w, h)
+            && (0..h).all(|r| {
+                (0..w).all(|c| {
+                    !grid.get_cell(r, c).is_black
+                        || self.orbit(r, c, w, h).iter().all(|&(r, c)| grid.get_cell(r, c).is_black)
+                })
+            })
+    }
+}
+
+// Settings for generated layouts.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LayoutOptions {
+    pub symmetry: Symmetry,
+    pub rules: LayoutRules,
+    // Share of squares to make black, from 0 to 1. A layout can end up
+    // denser when the rules call for it, up to `rules.max_black_ratio`.
+    pub density: f64,
+    // Budgets. Each try starts again from an empty grid.
+    pub max_tries: usize,
+    pub time_limit_ms: Option<u64>,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        LayoutOptions {
+            symmetry: Symmetry::default(),
+            rules: LayoutRules::default(),
+            density: 0.16, // Typical for open grids
+            max_tries: 100,
+            time_limit_ms: Some(5000),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    UnsupportedSymmetry { symmetry: Symmetry, width: usize, height: usize },
+    EmptyGrid { width: usize, height: usize },
+    // Neither side is as long as the shortest entry allowed
+    TooSmall { width: usize, height: usize, min_length: usize },
+    InvalidDensity(f64),
+    // The budgets ran out before any try met the rules
+    NoLayout { tries: usize },
+    TimedOut { tries: usize },
+    Cancelled { tries: usize },
+    // None of the layouts handed to the solver could be filled
+    Unfillable { layouts: usize },
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::UnsupportedSymmetry { symmetry, width, height } => {
+                write!(f, "{} symmetry needs a square grid, not {}x{}", symmetry.name(), width, height)
+            }
+            LayoutError::EmptyGrid { width, height } => {
+                write!(f, "a layout needs at least one row and column, not {}x{}", width, height)
+            }
+            LayoutError::TooSmall { width, height, min_length } => {
+                write!(f, "a {}x{} grid has no room for an entry of {} letters", width, height, min_length)
+            }
+            LayoutError::InvalidDensity(density) => {
+                write!(f, "density must be between 0 and 1, not {}", density)
+            }
+            LayoutError::NoLayout { tries } => {
+                write!(f, "no layout met the rules in {} tries", tries)
+            }
+            LayoutError::TimedOut { tries } => {
+                write!(f, "no layout met the rules before the time limit ({} tries)", tries)
+            }
+            LayoutError::Cancelled { tries } => {
+                write!(f, "layout generation was cancelled ({} tries)", tries)
+            }
+            LayoutError::Unfillable { layouts } => {
+                write!(f, "none of the {} layouts generated could be filled", layouts)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+// What generation threw away on the way to a layout, and why.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Rejections {
+    pub broke_rules: usize,      // Tries that ended short of the density or the rules
+    pub missing_lengths: usize,  // Tries that ended with an entry no word is as long as
+    pub dead_ends: usize,        // Black squares taken back because the grid stopped propagating
+    pub never_propagated: usize, // Tries that ended without the grid ever propagating
+    pub unfillable: usize,       // Layouts the solver showed have no fill
+    pub gave_up: usize,          // Layouts the solver ran out of budget on
+}
 
 pub struct LayoutGenerator {
     width: usize,
     height: usize,
+    seed: u64,
+    options: LayoutOptions,
+    dictionary: Option<Rc<Dawg>>,
+    cancel: Option<Rc<dyn Fn() -> bool>>,
 }
 
 impl LayoutGenerator {
     pub fn new(width: usize, height: usize) -> Self {
-        Self { width, height }
+        Self {
+            width,
+            height,
+            seed: random_seed(),
+            options: LayoutOptions::default(),
+            dictionary: None,
+            cancel: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: LayoutOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // The same seed and size always give the same layout.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // Also weighs black squares against a dictionary: every entry must be
+    // a length the dictionary has words of, and the grid must still
+    // propagate after each square goes in.
+    pub fn with_dictionary(mut self, dawg: Rc<Dawg>) -> Self {
+        self.dictionary = Some(dawg);
+        self
+    }
+
+    // Checked between tries; once it returns true, generation stops with
+    // `Cancelled`. Solvers for the layouts take their own check.
+    pub fn with_cancel_check(mut self, cancel: Rc<dyn Fn() -> bool>) -> Self {
+        self.cancel = Some(cancel);
+        self
     }
 
-    pub fn generate(&self) -> Grid {
-        let mut rng = rand::thread_rng();
-        
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn generate(&self) -> Result<Grid, LayoutError> {
+        let mut layouts = self.layouts()?;
+        layouts.next().unwrap_or(Err(LayoutError::NoLayout { tries: 0 }))
+    }
+
+    // Layouts that meet the rules, one per successful try, until the
+    // budgets run out; the last item is then the error saying which.
+    pub fn layouts(&self) -> Result<Layouts<'_>, LayoutError> {
+        let (width, height) = (self.width, self.height);
+        let symmetry = self.options.symmetry;
+        if width == 0 || height == 0 {
+            return Err(LayoutError::EmptyGrid { width, height });
+        }
+        let min_length = self.options.rules.min_entry_length.unwrap_or(2).max(2);
+        if width.max(height) < min_length {
+            return Err(LayoutError::TooSmall { width, height, min_length });
+        }
+        if !symmetry.fits(width, height) {
+            return Err(LayoutError::UnsupportedSymmetry { symmetry, width, height });
+        }
+        if !(0.0..=1.0).contains(&self.options.density) {
+            return Err(LayoutError::InvalidDensity(self.options.density));
+        }
+        let deadline = self
+            .options
+            .time_limit_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
+
+        // lengths[n]: whether the dictionary has any word of n letters
+        let longest = width.max(height);
+        let mut lengths = vec![false; longest + 1];
+        if let Some(dawg) = &self.dictionary {
+            dawg.walk_matching(&[], 2..=longest, &mut |word, _| {
+                lengths[word.len()] = true;
+                true
+            });
+        }
+
+        Ok(Layouts {
+            generator: self,
+            rng: ChaCha8Rng::seed_from_u64(self.seed),
+            deadline,
+            lengths,
+            tries: 0,
+            done: false,
+            rejections: Rejections::default(),
+        })
+    }
+}
+
+pub struct Layouts<'a> {
+    generator: &'a LayoutGenerator,
+    rng: ChaCha8Rng,
+    deadline: Option<Instant>,
+    lengths: Vec<bool>,
+    tries: usize,
+    done: bool,
+    pub rejections: Rejections,
+}
+
+impl Iterator for Layouts<'_> {
+    type Item = Result<Grid, LayoutError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
         // Try to generate a valid grid. If we get stuck, retry from scratch.
-        loop {
-            let mut grid = Grid::new(self.width, self.height);
-            
-            // Target black square density: ~16% (typical for open grids)
-            let target_black_count = (self.width * self.height) as f64 * 0.16; 
-            let mut current_black_count = 0;
-            
-            let mut attempts = 0;
-            let max_attempts = 2000;
-
-            while (current_black_count as f64) < target_black_count && attempts < max_attempts {
-                attempts += 1;
-                
-                let r = rng.gen_range(0..self.height);
-                let c = rng.gen_range(0..self.width);
-                
-                if grid.get_cell(r, c).is_black {
-                    continue;
-                }
+        while self.tries < self.generator.options.max_tries {
+            if self.cancelled() {
+                self.done = true;
+                return Some(Err(LayoutError::Cancelled { tries: self.tries }));
+            }
+            self.tries += 1;
+            if let Some(grid) = self.try_layout() {
+                return Some(Ok(grid));
+            }
+            if self.timed_out() {
+                self.done = true;
+                return Some(Err(LayoutError::TimedOut { tries: self.tries }));
+            }
+        }
+        self.done = true;
+        Some(Err(LayoutError::NoLayout { tries: self.tries }))
+    }
+}
+
+impl Layouts<'_> {
+    // One pass of placing black squares at random, keeping each that the
+    // rules allow. None if the pass ends without a valid layout.
+    fn try_layout(&mut self) -> Option<Grid> {
+        let generator = self.generator;
+        let (width, height) = (generator.width, generator.height);
+        let symmetry = generator.options.symmetry;
+        let rules = &generator.options.rules;
+        let mut grid = Grid::new(width, height);
+
+        let target_black_count = (width * height) as f64 * generator.options.density;
+        let mut current_black_count = 0;
 
-                let sym_r = self.height - 1 - r;
-                let sym_c = self.width - 1 - c;
+        let mut attempts = 0;
+        let max_attempts = 2000;
+        let mut violations = rules.check(&grid);
+        let mut missing = self.missing_length(&grid);
+        // The grid so far, propagated. None until it first propagates;
+        // from then on it is kept up to date square by square.
+        let mut solver = if missing { None } else { self.propagated(&grid) };
+        let dictionary = generator.dictionary.is_some();
+        // Propagation is the slow check, so a try that keeps failing it
+        // is cut short
+        let mut dead_ends_in_a_row = 0;
+        let max_dead_ends_in_a_row = 50;
 
-                // Tentatively set black
+        // Keep going past the target while entries are still too long, or
+        // the grid doesn't propagate yet
+        while ((current_black_count as f64) < target_black_count
+            || !violations.is_empty()
+            || (dictionary && solver.is_none()))
+            && attempts < max_attempts
+            && dead_ends_in_a_row < max_dead_ends_in_a_row
+        {
+            attempts += 1;
+            if self.timed_out() {
+                return None;
+            }
+
+            let r = self.rng.gen_range(0..height);
+            let c = self.rng.gen_range(0..width);
+
+            if grid.get_cell(r, c).is_black {
+                continue;
+            }
+
+            // Black squares only ever go in whole orbits, so the rest
+            // of this one is white too
+            let orbit = symmetry.orbit(r, c, width, height);
+
+            // Tentatively set black
+            for &(r, c) in &orbit {
                 grid.set_black(r, c, true);
-                grid.set_black(sym_r, sym_c, true);
-
-                // Check constraints
-                if self.is_valid_layout(&grid) {
-                    // Keep it
-                    current_black_count = grid.cells.iter().filter(|c| c.is_black).count();
-                } else {
-                    // Revert
+            }
+
+            // Check constraints. A square is a dead end if the grid
+            // propagated before it and doesn't after; while the grid is
+            // still too open to propagate, or has entries of lengths with
+            // no words, there is nothing to go by.
+            let after = rules.check(&grid);
+            let keep = after.iter().all(fixed_by_more_black) && {
+                let missing_after = self.missing_length(&grid);
+                match &mut solver {
+                    Some(propagated) if !missing_after => {
+                        if propagated.place_black(&orbit) {
+                            dead_ends_in_a_row = 0;
+                            true
+                        } else {
+                            self.rejections.dead_ends += 1;
+                            dead_ends_in_a_row += 1;
+                            false
+                        }
+                    }
+                    _ => {
+                        solver = if missing_after { None } else { self.propagated(&grid) };
+                        missing = missing_after;
+                        true
+                    }
+                }
+            };
+            if keep {
+                current_black_count = grid.cells.iter().filter(|c| c.is_black).count();
+                violations = after;
+            } else {
+                // Revert
+                for &(r, c) in &orbit {
                     grid.set_black(r, c, false);
-                    grid.set_black(sym_r, sym_c, false);
                 }
             }
-            
-            // Final check
-            if self.is_valid_layout(&grid) {
-                return grid;
-            }
         }
-    }
 
-    fn is_valid_layout(&self, grid: &Grid) -> bool {
-        // 1. Check connectivity
-        if !self.check_connectivity(grid) {
-            return false;
+        // Final check
+        let dense_enough = current_black_count as f64 >= target_black_count;
+        // e.g. every square black
+        let no_entries = !runs(&grid).iter().any(|run| run.length > 1);
+        if !dense_enough || !violations.is_empty() || no_entries {
+            self.rejections.broke_rules += 1;
+            return None;
         }
-
-        // 2. Check minimum word length (3)
-        if !self.check_min_word_length(grid) {
-            return false;
+        if missing {
+            self.rejections.missing_lengths += 1;
+            return None;
         }
-
-        true
+        if dictionary && solver.is_none() {
+            self.rejections.never_propagated += 1;
+            return None;
+        }
+        Some(grid)
     }
 
-    fn check_connectivity(&self, grid: &Grid) -> bool {
-        let total_white = grid.cells.iter().filter(|c| !c.is_black).count();
-        if total_white == 0 { return true; }
+    // Whether some entry is a length the dictionary has no words of.
+    fn missing_length(&self, grid: &Grid) -> bool {
+        self.generator.dictionary.is_some()
+            && runs(grid).iter().any(|run| run.length > 1 && !self.lengths[run.length])
+    }
 
-        // Find first white cell
-        let start_node = match grid.cells.iter().position(|c| !c.is_black) {
-            Some(idx) => idx,
-            None => return true,
+    // A solver for `grid` with the dictionary, if it propagates.
+    fn propagated(&self, grid: &Grid) -> Option<Solver> {
+        let dawg = self.generator.dictionary.as_ref()?;
+        let options = SolveOptions {
+            backjumping: false, // Nothing is decided, so nothing to explain
+            ..SolveOptions::default()
         };
-        
-        let (start_r, start_c) = (start_node / grid.width, start_node % grid.width);
-
-        let mut visited = vec![false; grid.width * grid.height];
-        let mut queue = VecDeque::new();
-        queue.push_back((start_r, start_c));
-        visited[start_node] = true;
-        let mut count = 0;
-
-        while let Some((r, c)) = queue.pop_front() {
-            count += 1;
-
-            // Neighbors
-            let dr = [0, 0, 1, -1];
-            let dc = [1, -1, 0, 0];
-
-            for i in 0..4 {
-                let nr = r as i32 + dr[i];
-                let nc = c as i32 + dc[i];
-
-                if nr >= 0 && nr < grid.height as i32 && nc >= 0 && nc < grid.width as i32 {
-                    let nr = nr as usize;
-                    let nc = nc as usize;
-                    let idx = grid.get_index(nr, nc);
-                    if !grid.cells[idx].is_black && !visited[idx] {
-                        visited[idx] = true;
-                        queue.push_back((nr, nc));
+        let mut solver = Solver::with_options(grid.clone(), dawg.clone(), options);
+        solver.propagate().then_some(solver)
+    }
+
+    fn timed_out(&self) -> bool {
+        self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.generator.cancel.as_ref().is_some_and(|cancel| cancel())
+    }
+
+    // What is left of `time_limit_ms`, if there is one. Zero once it has
+    // run out.
+    pub fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn tries(&self) -> usize {
+        self.tries
+    }
+}
+
+// Whether adding black squares can still clear a violation, so a
+// placement that leaves it behind is worth keeping.
+fn fixed_by_more_black(violation: &Violation) -> bool {
+    violation.rule == Rule::MaxEntryLength
+}
+
+// A fresh seed for when the caller didn't pick one. Kept within u32 so it
+// survives a round trip through JS numbers and back into the API.
+pub fn random_seed() -> u64 {
+    rand::random::<u32>() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generator(width: usize, height: usize, options: LayoutOptions) -> LayoutGenerator {
+        LayoutGenerator::new(width, height).with_options(options).with_seed(21)
+    }
+
+    #[test]
+    fn orbits_map_onto_themselves() {
+        for symmetry in Symmetry::MIRRORED.into_iter().chain([Symmetry::None]) {
+            for (width, height) in [(5, 5), (6, 6), (4, 7)] {
+                if !symmetry.fits(width, height) {
+                    continue;
+                }
+                for (r, c) in (0..height).flat_map(|r| (0..width).map(move |c| (r, c))) {
+                    let orbit = symmetry.orbit(r, c, width, height);
+                    assert!(orbit.contains(&(r, c)));
+                    assert!(orbit.len() <= if symmetry == Symmetry::Rotational90 { 4 } else { 2 });
+                    for &(r, c) in &orbit {
+                        assert_eq!(symmetry.orbit(r, c, width, height), orbit);
+                    }
+
+                    let mut grid = Grid::new(width, height);
+                    for &(r, c) in &orbit {
+                        grid.set_black(r, c, true);
+                    }
+                    assert!(symmetry.holds(&grid));
+                    // One square short of the orbit breaks it
+                    if orbit.len() > 1 {
+                        grid.set_black(r, c, false);
+                        assert!(!symmetry.holds(&grid));
                     }
                 }
             }
         }
-
-        count == total_white
+        assert!(!Symmetry::Rotational90.fits(4, 7) && !Symmetry::Diagonal.fits(4, 7));
+        assert!(!Symmetry::Rotational90.holds(&Grid::new(4, 7)));
     }
 
-    fn check_min_word_length(&self, grid: &Grid) -> bool {
-        // Check rows
-        for r in 0..grid.height {
-            let mut len = 0;
-            for c in 0..grid.width {
-                if grid.get_cell(r, c).is_black {
-                    if len > 0 && len < 3 { return false; }
-                    len = 0;
-                } else {
-                    len += 1;
+    #[test]
+    fn generated_layouts_hold_their_symmetry() {
+        for symmetry in Symmetry::MIRRORED.into_iter().chain([Symmetry::None]) {
+            for (width, height) in [(7, 7), (9, 7)] {
+                if !symmetry.fits(width, height) {
+                    continue;
                 }
+                let options = LayoutOptions {
+                    symmetry,
+                    time_limit_ms: None,
+                    ..LayoutOptions::default()
+                };
+                let grid = generator(width, height, options.clone()).generate().unwrap();
+                assert!(symmetry.holds(&grid), "{}", symmetry.name());
+                assert!(options.rules.check(&grid).is_empty());
+                assert!(grid.cells.iter().any(|cell| cell.is_black));
             }
-            if len > 0 && len < 3 { return false; }
         }
+    }
 
-        // Check cols
-        for c in 0..grid.width {
-            let mut len = 0;
-            for r in 0..grid.height {
-                if grid.get_cell(r, c).is_black {
-                    if len > 0 && len < 3 { return false; }
-                    len = 0;
-                } else {
-                    len += 1;
-                }
-            }
-            if len > 0 && len < 3 { return false; }
+    #[test]
+    fn bad_sizes_and_densities_are_rejected() {
+        let error = |width, height, options| generator(width, height, options).generate().err();
+        let rotational_90 = LayoutOptions {
+            symmetry: Symmetry::Rotational90,
+            ..LayoutOptions::default()
+        };
+        assert_eq!(
+            error(4, 7, rotational_90),
+            Some(LayoutError::UnsupportedSymmetry {
+                symmetry: Symmetry::Rotational90,
+                width: 4,
+                height: 7
+            })
+        );
+        assert_eq!(error(0, 7, LayoutOptions::default()), Some(LayoutError::EmptyGrid { width: 0, height: 7 }));
+        assert_eq!(
+            error(2, 2, LayoutOptions::default()),
+            Some(LayoutError::TooSmall {
+                width: 2,
+                height: 2,
+                min_length: 3
+            })
+        );
+        for density in [-0.1, 1.5, f64::NAN] {
+            let options = LayoutOptions {
+                density,
+                ..LayoutOptions::default()
+            };
+            assert!(matches!(error(7, 7, options), Some(LayoutError::InvalidDensity(_))));
         }
+    }
 
-        true
+    #[test]
+    fn generation_stops_when_the_budgets_run_out() {
+        // Any black square in a 3x3 grid leaves an entry under 3 letters
+        let options = LayoutOptions {
+            max_tries: 10,
+            time_limit_ms: None,
+            ..LayoutOptions::default()
+        };
+        let generator = generator(3, 3, options);
+        assert_eq!(generator.generate().err(), Some(LayoutError::NoLayout { tries: 10 }));
+        let mut layouts = generator.layouts().unwrap();
+        assert!(matches!(layouts.next(), Some(Err(LayoutError::NoLayout { .. }))));
+        assert!(layouts.next().is_none());
+        assert_eq!(layouts.rejections.broke_rules, 10);
+
+        let options = LayoutOptions {
+            max_tries: usize::MAX,
+            time_limit_ms: Some(0),
+            ..LayoutOptions::default()
+        };
+        assert!(matches!(self::generator(3, 3, options).generate(), Err(LayoutError::TimedOut { .. })));
     }
 }
@@ -1,22 +1,63 @@
 use wasm_bindgen::prelude::*;
 use crate::grid::{Grid, GridConfig};
-use crate::dawg::Dawg;
-use crate::solver::Solver;
-use crate::layout::LayoutGenerator;
+use crate::dawg::{parse_entry, Dawg};
+use crate::solver::{SlotLocator, SolveOptions, SolveOutcome, Solver, ValueOrder, VariableOrder};
+use crate::response::{
+    CompareResponse, DiagnoseResponse, EnumerateResponse, ErrorKind, HeuristicRun, SolveError, SolveResponse, SolveStatus, StepResponse,
+    LayoutResponse, SuggestResponse, ValidateResponse, WordsResponse,
+};
+use crate::layout::{LayoutGenerator, LayoutOptions};
+use crate::rules::{LayoutRules, LayoutStats};
+use crate::pattern::PatternQuery;
+use crate::anagram::AnagramQuery;
+use crate::fuzzy::FuzzyQuery;
+use crate::fillable::FillableOptions;
+use crate::theme::ThemeWords;
+use js_sys::Int32Array;
+use std::rc::Rc;
 
-mod domain;
-mod grid;
-mod solver;
-mod dawg;
-mod layout;
+pub mod domain;
+pub mod grid;
+pub mod solver;
+pub mod dawg;
+pub mod layout;
+pub mod rules;
+pub mod theme;
+pub mod response;
+pub mod word_index;
+pub mod diagnose;
+pub mod pattern;
+pub mod anagram;
+pub mod fuzzy;
+pub mod fillable;
 
 #[wasm_bindgen]
 pub fn init_panic_hook() {
     console_error_panic_hook::set_once();
 }
 
+// Builds the binary dictionary blob in the browser, for word lists that
+// didn't come through the offline factory. Lines may be `WORD;SCORE`.
+#[wasm_bindgen]
+pub fn pack_dictionary(words: Vec<String>) -> Vec<u8> {
+    Dawg::from_entries(&words).to_bytes()
+}
+
+// A solver session. The dictionary is loaded once and reused by every
+// solve and query until it is cleared or replaced.
 #[wasm_bindgen]
 pub struct CrosswordSolver {
+    dawg: Rc<Dawg>,
+    cancel_flag: Option<Int32Array>,
+    // The step-wise search in progress, if any. It keeps the dictionary it
+    // started with even if the session's is changed meanwhile.
+    search: Option<Solver>,
+}
+
+impl Default for CrosswordSolver {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[wasm_bindgen]
@@ -24,82 +65,552 @@ impl CrosswordSolver {
     #[wasm_bindgen(constructor)]
     pub fn new() -> CrosswordSolver {
         init_panic_hook();
-        CrosswordSolver {}
-    }
-
-    pub fn generate_grid(&self, width: usize, height: usize) -> String {
-        let generator = LayoutGenerator::new(width, height);
-        let grid = generator.generate();
-        
-        // Convert to GridConfig JSON
-        let mut black_cells = Vec::new();
-        for r in 0..height {
-            for c in 0..width {
-                if grid.get_cell(r, c).is_black {
-                    black_cells.push((r, c));
-                }
+        CrosswordSolver {
+            dawg: Rc::new(Dawg::new()),
+            cancel_flag: None,
+            search: None,
+        }
+    }
+
+    // Registers an Int32Array (backed by a SharedArrayBuffer) that another
+    // context can set to a non-zero value with Atomics.store to stop a
+    // running solve. The solver only ever reads it, so reset it to 0 before
+    // starting the next solve. Pass undefined to remove it.
+    pub fn set_cancel_flag(&mut self, flag: Option<Int32Array>) {
+        self.cancel_flag = flag;
+    }
+
+    // Merges a packed dictionary blob into the session. Returns the total
+    // word count afterwards. A word in both keeps its higher score. Into
+    // an empty session the blob loads as is; otherwise the graph is
+    // rebuilt, in time linear in the combined word count.
+    pub fn load_dictionary(&mut self, dictionary: &[u8]) -> Result<usize, JsError> {
+        let loaded = Dawg::from_bytes(dictionary)?;
+        if self.dawg.word_count == 0 {
+            self.dawg = Rc::new(loaded);
+        } else {
+            self.dawg = Rc::new(self.dawg.merged(&loaded));
+        }
+        Ok(self.dawg.word_count)
+    }
+
+    // Drops the current dictionary and loads `dictionary` in its place.
+    pub fn replace_dictionary(&mut self, dictionary: &[u8]) -> Result<usize, JsError> {
+        self.dawg = Rc::new(Dawg::from_bytes(dictionary)?);
+        Ok(self.dawg.word_count)
+    }
+
+    // Accepts plain words or Broda `WORD;SCORE` entries; plain words get
+    // the default score, and a word already there keeps the higher score.
+    // Only the new words are sorted, but the graph is rebuilt, so each call
+    // costs time linear in the session's word count: add words in batches.
+    pub fn add_words(&mut self, words: Vec<String>) -> usize {
+        self.dawg = Rc::new(self.dawg.with_scored_words(words.iter().filter_map(|w| parse_entry(w))));
+        self.dawg.word_count
+    }
+
+    // Also a rebuild, like `add_words`.
+    pub fn remove_words(&mut self, words: Vec<String>) -> usize {
+        self.dawg = Rc::new(self.dawg.without_words(&words));
+        self.dawg.word_count
+    }
+
+    pub fn clear_dictionary(&mut self) {
+        self.dawg = Rc::new(Dawg::new());
+    }
+
+    // The session dictionary as a packed blob, e.g. for caching in IndexedDB.
+    pub fn export_dictionary(&self) -> Vec<u8> {
+        self.dawg.to_bytes()
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.dawg.word_count
+    }
+
+    // Approximate heap bytes held by the session dictionary.
+    pub fn memory_usage(&self) -> usize {
+        self.dawg.memory_usage()
+    }
+
+    pub fn is_word(&self, word: &str) -> bool {
+        self.dawg.is_word(word)
+    }
+
+    pub fn score(&self, word: &str) -> Option<u8> {
+        self.dawg.score(word)
+    }
+
+    pub fn is_valid_prefix(&self, prefix: &str) -> bool {
+        self.dawg.is_valid_prefix(prefix)
+    }
+
+    pub fn next_chars(&self, prefix: &str) -> String {
+        self.dawg
+            .next_chars(prefix)
+            .map(|chars| chars.into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    // Words matching a pattern such as `C?[AEIOU]T`. `query_json` is
+    // `{ "pattern", "min_length"?, "max_length"?, "min_score"?, "order"?,
+    // "limit"? }`, with `order` "alphabetical" (default) or "score".
+    pub fn find_words(&self, query_json: String) -> String {
+        self.run_find_words(&query_json).to_json()
+    }
+
+    // Words spelled from a set of tiles. `query_json` is `{ "letters",
+    // "blanks"?, "partial"?, "min_length"?, "min_score"?, "order"?,
+    // "limit"? }`; `?` in `letters` is a blank. Without `partial` every
+    // tile must be used.
+    pub fn find_anagrams(&self, query_json: String) -> String {
+        self.run_find_anagrams(&query_json).to_json()
+    }
+
+    // Dictionary words within a few edits of a word, for spellchecking and
+    // near misses. `query_json` is `{ "word", "max_distance"? (2),
+    // "length"?, "min_score"?, "order"?, "limit"? }`; results carry their
+    // `distance` and come closest first unless `order` says otherwise.
+    pub fn find_similar(&self, query_json: String) -> String {
+        self.run_find_similar(&query_json).to_json()
+    }
+
+    // Pass a seed (e.g. derived from the date) to get the same layout every
+    // time; the seed used is returned with the layout either way.
+    // `options_json` is `{ "symmetry"?, "rules"?, "density"?, "max_tries"?,
+    // "time_limit_ms"? }`. The result is a grid config plus `status`, and
+    // `error` if no layout could be made within the budgets.
+    pub fn generate_grid(&self, width: usize, height: usize, seed: Option<u32>, options_json: Option<String>) -> String {
+        self.run_generate(width, height, seed, options_json.as_deref()).to_json()
+    }
+
+    // Checks a hand-drawn layout (a grid config) against `rules_json`, the
+    // same rules `generate_grid` takes, defaults included. Lists each
+    // violation with the cells involved, plus the layout's word count,
+    // length histogram, open squares and symmetries under `stats`.
+    pub fn validate_layout(&self, grid_json: String, rules_json: Option<String>) -> String {
+        self.run_validate(&grid_json, rules_json.as_deref()).to_json()
+    }
+
+    // Like `generate_grid`, but only hands back a layout the session
+    // dictionary can fill, with that fill under `fill`. Black squares are
+    // placed with the dictionary in mind, and each finished layout goes to
+    // the solver. `options_json` takes the `generate_grid` options plus
+    // `"solve"` (solve options, bounding each fill attempt) and
+    // `"max_layouts"`; `"time_limit_ms"` covers the whole call, fill
+    // attempts included. `rejections` counts what was thrown away and why.
+    pub fn generate_fillable_grid(&self, width: usize, height: usize, seed: Option<u32>, options_json: Option<String>) -> String {
+        self.run_generate_fillable(width, height, seed, options_json.as_deref()).to_json()
+    }
+
+    pub fn solve(&self, grid_json: String, options_json: Option<String>) -> String {
+        self.run_solve(&grid_json, options_json.as_deref()).to_json()
+    }
+
+    // Fits the theme ("Soul") words first, then fills the rest of the grid
+    // from the session dictionary. `theme_json` is
+    // `{ "required": [...], "preferred": [...] }`.
+    pub fn solve_themed(&self, grid_json: String, theme_json: String, options_json: Option<String>) -> String {
+        self.run_solve_themed(&grid_json, &theme_json, options_json.as_deref()).to_json()
+    }
+
+    // Counts fills, stopping at `limit`. `complete` in the result says
+    // whether the count is exact, so a complete count of 1 flags a grid
+    // with only one possible fill.
+    pub fn count_solutions(&self, grid_json: String, limit: usize, options_json: Option<String>) -> String {
+        self.run_enumerate(&grid_json, options_json.as_deref(), Some(limit), 0).to_json()
+    }
+
+    // The `k` best fills by total slot score. Every fill the search can
+    // reach is considered, so set a budget on anything but small grids.
+    pub fn best_fills(&self, grid_json: String, k: usize, options_json: Option<String>) -> String {
+        self.run_enumerate(&grid_json, options_json.as_deref(), None, k).to_json()
+    }
+
+    // Fill assistant: the words that fit one slot of a partly filled grid
+    // and keep every other slot fillable after propagation, each with how
+    // many candidates it leaves the crossing slots. `slot_json` is
+    // `{ "slot": id }` or `{ "r", "c", "is_across" }` naming any cell of
+    // the slot. Ranked by the options' `value_order` ("lcv" puts the words
+    // leaving the crossings most room first) and cut to `limit`.
+    pub fn suggest_words(
+        &self,
+        grid_json: String,
+        slot_json: String,
+        limit: Option<usize>,
+        options_json: Option<String>,
+    ) -> String {
+        self.run_suggest(&grid_json, &slot_json, limit, options_json.as_deref()).to_json()
+    }
+
+    // Explains why a grid has no fill: slots no word fits given their
+    // fixed letters, crossings whose slots share no letter, and a minimal
+    // set of slots and fixed letters that can't hold together. Budgets in
+    // the options apply to each check; a time limit to the whole run.
+    pub fn diagnose(&self, grid_json: String, options_json: Option<String>) -> String {
+        self.run_diagnose(&grid_json, options_json.as_deref()).to_json()
+    }
+
+    // Solves the grid once per combination of variable and value order,
+    // each under the same options and budgets, and returns every run's
+    // status, fill quality and stats side by side.
+    pub fn compare_heuristics(&self, grid_json: String, options_json: Option<String>) -> String {
+        self.run_compare(&grid_json, options_json.as_deref()).to_json()
+    }
+
+    // Starts a step-wise search, replacing any search in progress. Advance
+    // it with `step`; nothing is tried until then. Returns the initial
+    // snapshot, or an error response if the request is invalid.
+    pub fn start_search(&mut self, grid_json: String, options_json: Option<String>) -> String {
+        match parse_request(&grid_json, options_json.as_deref()) {
+            Ok((grid, options)) => {
+                let solver = self.new_solver(grid, options);
+                let snapshot = StepResponse::new(&solver, solver.status());
+                self.search = Some(solver);
+                snapshot.to_json()
+            }
+            Err(e) => {
+                self.search = None;
+                SolveResponse::from(e).to_json()
+            }
+        }
+    }
+
+    // Tries up to `steps` more values, then returns the current domains of
+    // every cell and the decision depth, so a worker can animate the fill
+    // and yield between slices.
+    pub fn step(&mut self, steps: usize) -> String {
+        match self.search.as_mut() {
+            Some(solver) => {
+                let status = solver.step(steps);
+                StepResponse::new(solver, status).to_json()
+            }
+            None => SolveResponse::error(ErrorKind::NoSearch, "No search in progress".to_string()).to_json(),
+        }
+    }
+
+    // Runs the search in progress to its next fill, distinct from every
+    // fill it returned before. A `failed` status means there are no more.
+    pub fn next_solution(&mut self) -> String {
+        match self.search.as_mut() {
+            Some(solver) => {
+                solver.next_solution();
+                StepResponse::new(solver, solver.status()).to_json()
             }
+            None => SolveResponse::error(ErrorKind::NoSearch, "No search in progress".to_string()).to_json(),
         }
+    }
+
+    pub fn stop_search(&mut self) {
+        self.search = None;
+    }
+}
+
+impl CrosswordSolver {
+    fn run_find_words(&self, query_json: &str) -> WordsResponse {
+        let query: PatternQuery = match serde_json::from_str(query_json) {
+            Ok(query) => query,
+            Err(e) => return SolveError::bad_json("query JSON", e).into(),
+        };
+        match pattern::find_words(&self.dawg, &query) {
+            Ok(found) => found.into(),
+            Err(e) => SolveError::from(e).into(),
+        }
+    }
 
-        let config = GridConfig {
-            width,
-            height,
-            black_cells,
-            fixed_cells: vec![],
-        };
-
-        serde_json::to_string(&config).unwrap_or_default()
-    }
-
-    pub fn solve(&self, grid_json: String, words: Vec<String>) -> String {
-        let config: GridConfig = match serde_json::from_str(&grid_json) {
-            Ok(c) => c,
-            Err(e) => return format!("{{ \"status\": \"error\", \"message\": \"Invalid JSON: {}\" }}", e),
-        };
-
-        let mut grid = Grid::new(config.width, config.height);
-        for (r, c) in config.black_cells {
-            grid.set_black(r, c, true);
-        }
-        // Handle fixed cells if any (pre-filled)
-        for (r, c, char_val) in config.fixed_cells {
-            let cell = grid.get_cell_mut(r, c);
-            cell.domain = domain::Domain::from_char(char_val);
-            cell.fixed_char = Some(char_val);
-        }
-
-        let mut dawg = Dawg::new();
-        for word in words {
-            dawg.insert(&word);
-        }
-
-        let mut solver = Solver::new(grid, dawg);
-        
-        match solver.solve() {
-            Some(solution) => {
-                // Convert solution to a simplified format for JS
-                let mut output_grid = vec![vec![' '; solution.width]; solution.height];
-                for r in 0..solution.height {
-                    for c in 0..solution.width {
-                        let cell = solution.get_cell(r, c);
-                        if !cell.is_black {
-                            if let Some(ch) = cell.domain.is_singleton() {
-                                output_grid[r][c] = ch;
-                            } else {
-                                output_grid[r][c] = '?'; // Should not happen if solved
-                            }
-                        } else {
-                            output_grid[r][c] = '#';
-                        }
-                    }
-                }
-                
-                let json = serde_json::to_string(&output_grid).unwrap_or_default();
-                format!("{{ \"status\": \"success\", \"grid\": {} }}", json)
+    fn run_find_anagrams(&self, query_json: &str) -> WordsResponse {
+        let query: AnagramQuery = match serde_json::from_str(query_json) {
+            Ok(query) => query,
+            Err(e) => return SolveError::bad_json("query JSON", e).into(),
+        };
+        match anagram::find_anagrams(&self.dawg, &query) {
+            Ok(found) => found.into(),
+            Err(e) => SolveError::from(e).into(),
+        }
+    }
+
+    fn run_find_similar(&self, query_json: &str) -> WordsResponse {
+        let query: FuzzyQuery = match serde_json::from_str(query_json) {
+            Ok(query) => query,
+            Err(e) => return SolveError::bad_json("query JSON", e).into(),
+        };
+        match fuzzy::find_similar(&self.dawg, &query) {
+            Ok(found) => found.into(),
+            Err(e) => SolveError::from(e).into(),
+        }
+    }
+
+    fn run_generate(&self, width: usize, height: usize, seed: Option<u32>, options_json: Option<&str>) -> LayoutResponse {
+        let options: LayoutOptions = match options_json {
+            None => LayoutOptions::default(),
+            Some(json) => match serde_json::from_str(json) {
+                Ok(options) => options,
+                Err(e) => return SolveError::bad_json("layout options JSON", e).into(),
+            },
+        };
+        let mut generator = LayoutGenerator::new(width, height).with_options(options);
+        if let Some(seed) = seed {
+            generator = generator.with_seed(seed as u64);
+        }
+        match generator.generate() {
+            Ok(grid) => LayoutResponse::success(layout_config(&grid, generator.seed())),
+            Err(e) => SolveError::from(e).into(),
+        }
+    }
+
+    fn run_generate_fillable(&self, width: usize, height: usize, seed: Option<u32>, options_json: Option<&str>) -> LayoutResponse {
+        let options: FillableOptions = match options_json {
+            None => FillableOptions::default(),
+            Some(json) => match serde_json::from_str(json) {
+                Ok(options) => options,
+                Err(e) => return SolveError::bad_json("layout options JSON", e).into(),
+            },
+        };
+        let mut generator = LayoutGenerator::new(width, height)
+            .with_options(options.layout)
+            .with_dictionary(self.dawg.clone());
+        if let Some(seed) = seed {
+            generator = generator.with_seed(seed as u64);
+        }
+        if let Some(cancel) = self.cancel_check() {
+            generator = generator.with_cancel_check(cancel);
+        }
+        let run = fillable::generate_fillable(&generator, options.max_layouts, &options.solve, |grid, solve| {
+            self.new_solver(grid, solve)
+        });
+
+        let mut response = match run.result {
+            Ok(filled) => {
+                let mut response = LayoutResponse::success(layout_config(&filled.layout, generator.seed()));
+                response.fill = Some(SolveResponse::success(&filled.solution, filled.entries, run.stats));
+                response
+            }
+            Err(e) => LayoutResponse::from(SolveError::from(e)),
+        };
+        response.rejections = Some(run.rejections);
+        response
+    }
+
+    fn run_validate(&self, grid_json: &str, rules_json: Option<&str>) -> ValidateResponse {
+        let config: GridConfig = match serde_json::from_str(grid_json) {
+            Ok(config) => config,
+            Err(e) => return SolveError::bad_json("JSON", e).into(),
+        };
+        let rules: LayoutRules = match rules_json {
+            None => LayoutRules::default(),
+            Some(json) => match serde_json::from_str(json) {
+                Ok(rules) => rules,
+                Err(e) => return SolveError::bad_json("rules JSON", e).into(),
             },
-            None => {
-                format!("{{ \"status\": \"failed\", \"message\": \"No solution found\" }}")
+        };
+        match Grid::from_config(&config) {
+            Ok(grid) => ValidateResponse::new(rules.check(&grid), LayoutStats::of(&grid)),
+            Err(e) => SolveError::from(e).into(),
+        }
+    }
+
+    fn new_solver(&self, grid: Grid, options: SolveOptions) -> Solver {
+        let mut solver = Solver::with_options(grid, self.dawg.clone(), options);
+        if let Some(cancel) = self.cancel_check() {
+            solver.set_cancel_check(cancel);
+        }
+        solver
+    }
+
+    // Whether the registered cancel flag has been set, if there is one.
+    fn cancel_check(&self) -> Option<Rc<dyn Fn() -> bool>> {
+        let flag = self.cancel_flag.clone()?;
+        Some(Rc::new(move || js_sys::Atomics::load(&flag, 0).is_ok_and(|v| v != 0)))
+    }
+
+    fn run_solve(&self, grid_json: &str, options_json: Option<&str>) -> SolveResponse {
+        match parse_request(grid_json, options_json) {
+            Ok((grid, options)) => self.solve_grid(grid, options),
+            Err(e) => e.into(),
+        }
+    }
+
+    fn solve_grid(&self, grid: Grid, options: SolveOptions) -> SolveResponse {
+        let mut solver = self.new_solver(grid, options);
+        let outcome = solver.solve();
+        let slots = match &outcome {
+            SolveOutcome::Solved(grid) | SolveOutcome::GaveUp { best_partial: grid, .. } => solver.slot_entries(grid),
+            SolveOutcome::Unsatisfiable => Vec::new(),
+        };
+        let mut response = SolveResponse::from_outcome(&outcome, slots, solver.take_stats());
+        response.seed = solver.seed();
+        response
+    }
+
+    fn run_compare(&self, grid_json: &str, options_json: Option<&str>) -> CompareResponse {
+        let (grid, options) = match parse_request(grid_json, options_json) {
+            Ok(parsed) => parsed,
+            Err(e) => return e.into(),
+        };
+        let mut runs = Vec::new();
+        for variable_order in [VariableOrder::Mrv, VariableOrder::DomWdeg, VariableOrder::SlotMrv] {
+            for value_order in [ValueOrder::Alphabetical, ValueOrder::Score, ValueOrder::Lcv] {
+                let options = SolveOptions {
+                    variable_order,
+                    value_order,
+                    ..options.clone()
+                };
+                runs.push(HeuristicRun::from(self.solve_grid(grid.clone(), options)));
+            }
+        }
+        CompareResponse {
+            status: SolveStatus::Success,
+            error: None,
+            runs,
+        }
+    }
+
+    fn run_suggest(&self, grid_json: &str, slot_json: &str, limit: Option<usize>, options_json: Option<&str>) -> SuggestResponse {
+        let (grid, options) = match parse_request(grid_json, options_json) {
+            Ok(parsed) => parsed,
+            Err(e) => return e.into(),
+        };
+        let at: SlotLocator = match serde_json::from_str(slot_json) {
+            Ok(at) => at,
+            Err(e) => return SolveError::bad_json("slot JSON", e).into(),
+        };
+        let mut solver = self.new_solver(grid, options);
+        let Some(slot) = solver.find_slot(&at) else {
+            return SolveError {
+                kind: ErrorKind::NoSuchSlot,
+                message: format!("No slot at {}", slot_json),
+            }
+            .into();
+        };
+        let found = solver.slot_options(slot);
+        SuggestResponse::new(slot, found, limit, solver.take_stats())
+    }
+
+    fn run_diagnose(&self, grid_json: &str, options_json: Option<&str>) -> DiagnoseResponse {
+        let (grid, options) = match parse_request(grid_json, options_json) {
+            Ok(parsed) => parsed,
+            Err(e) => return e.into(),
+        };
+        let solver = self.new_solver(grid, options);
+        DiagnoseResponse::new(diagnose::diagnose(&solver))
+    }
+
+    fn run_enumerate(&self, grid_json: &str, options_json: Option<&str>, limit: Option<usize>, keep: usize) -> EnumerateResponse {
+        let (grid, options) = match parse_request(grid_json, options_json) {
+            Ok(parsed) => parsed,
+            Err(e) => return e.into(),
+        };
+        let mut solver = self.new_solver(grid, options);
+        let found = solver.enumerate(limit, keep);
+        let stats = solver.take_stats();
+        EnumerateResponse::new(&solver, &found, stats)
+    }
+
+    fn run_solve_themed(&self, grid_json: &str, theme_json: &str, options_json: Option<&str>) -> SolveResponse {
+        let (grid, options) = match parse_request(grid_json, options_json) {
+            Ok(parsed) => parsed,
+            Err(e) => return e.into(),
+        };
+        let theme: ThemeWords = match serde_json::from_str(theme_json) {
+            Ok(t) => t,
+            Err(e) => return SolveError::bad_json("theme JSON", e).into(),
+        };
+
+        let solver = self.new_solver(grid, options);
+        let seed = solver.seed();
+        let fill = theme::solve_themed(solver, &theme);
+        let mut response = SolveResponse::from_outcome(&fill.outcome, fill.entries, fill.stats);
+        response.seed = seed;
+        response.placed = Some(fill.placed);
+        response.unplaced = Some(fill.unplaced);
+        response
+    }
+}
+
+// The config for a generated layout: its black squares, and the seed that
+// reproduces it.
+fn layout_config(grid: &Grid, seed: u64) -> GridConfig {
+    let mut black_cells = Vec::new();
+    for r in 0..grid.height {
+        for c in 0..grid.width {
+            if grid.get_cell(r, c).is_black {
+                black_cells.push((r, c));
             }
         }
     }
+    GridConfig {
+        width: grid.width,
+        height: grid.height,
+        black_cells,
+        fixed_cells: vec![],
+        seed: Some(seed),
+    }
+}
+
+// Parses and validates the grid and options shared by every solve call.
+fn parse_request(grid_json: &str, options_json: Option<&str>) -> Result<(Grid, SolveOptions), SolveError> {
+    let config: GridConfig =
+        serde_json::from_str(grid_json).map_err(|e| SolveError::bad_json("JSON", e))?;
+    let options = match options_json {
+        None => SolveOptions::default(),
+        Some(json) => serde_json::from_str(json).map_err(|e| SolveError::bad_json("options JSON", e))?,
+    };
+    let grid = Grid::from_config(&config)?;
+    Ok((grid, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(words: &[&str]) -> CrosswordSolver {
+        let mut solver = CrosswordSolver::new();
+        solver.add_words(words.iter().map(|w| w.to_string()).collect());
+        solver
+    }
+
+    fn blob(words: &[&str]) -> Vec<u8> {
+        pack_dictionary(words.iter().map(|w| w.to_string()).collect())
+    }
+
+    #[test]
+    fn adds_and_removes_words() {
+        let mut solver = session(&["CAT;30", "dog"]);
+        assert_eq!(solver.word_count(), 2);
+        assert_eq!(solver.score("CAT"), Some(30));
+        assert_eq!(solver.score("DOG"), Some(dawg::DEFAULT_SCORE));
+
+        // A word added again is still one word, with the higher score
+        assert_eq!(solver.add_words(vec!["cat;70".into(), "EMU".into(), "X-RAY".into()]), 3);
+        assert_eq!(solver.score("CAT"), Some(70));
+        assert_eq!(solver.add_words(vec!["CAT;10".into()]), 3);
+        assert_eq!(solver.score("CAT"), Some(70));
+
+        assert_eq!(solver.remove_words(vec!["cat".into(), "GNU".into()]), 2);
+        assert!(!solver.is_word("CAT"));
+        assert!(solver.is_word("DOG") && solver.is_word("EMU"));
+
+        solver.clear_dictionary();
+        assert_eq!(solver.word_count(), 0);
+        assert!(!solver.is_word("DOG"));
+    }
+
+    #[test]
+    fn merges_and_replaces_blobs() {
+        let mut solver = CrosswordSolver::new();
+        assert_eq!(solver.load_dictionary(&blob(&["CAT;30", "DOG"])).ok(), Some(2));
+        assert_eq!(solver.load_dictionary(&blob(&["CAT;80", "EMU;20"])).ok(), Some(3));
+        assert_eq!(solver.score("CAT"), Some(80));
+        assert_eq!(solver.score("EMU"), Some(20));
+
+        // Exported, the session loads back as it was
+        let exported = solver.export_dictionary();
+        let mut copy = CrosswordSolver::new();
+        assert_eq!(copy.load_dictionary(&exported).ok(), Some(3));
+        assert_eq!(copy.dawg.scored_words(), solver.dawg.scored_words());
+
+        assert_eq!(solver.replace_dictionary(&blob(&["GNU"])).ok(), Some(1));
+        assert!(solver.is_word("GNU"));
+        assert!(!solver.is_word("CAT"));
+    }
 }
@@ -0,0 +1,322 @@
+use crate::dawg::Dawg;
+use crate::domain::Domain;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+
+// Letters allowed at each position of a word, e.g. `C?[AEIOU]T`: a letter
+// stands for itself, `?` for any letter, `[...]` for any letter listed
+// (ranges like `A-F` allowed, `^` first to exclude them instead).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    pub positions: Vec<Domain>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternError {
+    UnexpectedChar { at: usize, ch: char },
+    UnclosedClass { at: usize },
+    EmptyClass { at: usize },
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::UnexpectedChar { at, ch } => {
+                write!(f, "unexpected {:?} at position {}", ch, at)
+            }
+            PatternError::UnclosedClass { at } => {
+                write!(f, "letter class opened at position {} is never closed", at)
+            }
+            PatternError::EmptyClass { at } => {
+                write!(f, "letter class at position {} allows no letter", at)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Pattern, PatternError> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut positions = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let ch = chars[i];
+            if ch == '?' {
+                positions.push(Domain::full());
+            } else if ch.is_ascii_alphabetic() {
+                positions.push(Domain::from_char(ch));
+            } else if ch == '[' {
+                let open = i;
+                let negated = chars.get(i + 1) == Some(&'^');
+                if negated {
+                    i += 1;
+                }
+                let mut class = Domain::empty();
+                loop {
+                    i += 1;
+                    match chars.get(i) {
+                        None => return Err(PatternError::UnclosedClass { at: open }),
+                        Some(']') => break,
+                        Some(&from) if from.is_ascii_alphabetic() => {
+                            let to = match (chars.get(i + 1), chars.get(i + 2)) {
+                                (Some('-'), Some(&to)) if to.is_ascii_alphabetic() => {
+                                    i += 2;
+                                    to
+                                }
+                                _ => from,
+                            };
+                            let (from, to) = (from.to_ascii_uppercase(), to.to_ascii_uppercase());
+                            for letter in from..=to {
+                                class = class.union(Domain::from_char(letter));
+                            }
+                        }
+                        Some(&ch) => return Err(PatternError::UnexpectedChar { at: i, ch }),
+                    }
+                }
+                if negated {
+                    class = complement(class);
+                }
+                if class.is_empty() {
+                    return Err(PatternError::EmptyClass { at: open });
+                }
+                positions.push(class);
+            } else {
+                return Err(PatternError::UnexpectedChar { at: i, ch });
+            }
+            i += 1;
+        }
+        Ok(Pattern { positions })
+    }
+}
+
+fn complement(domain: Domain) -> Domain {
+    let mut rest = Domain::full();
+    for letter in domain.iter() {
+        rest.remove(letter);
+    }
+    rest
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WordOrder {
+    #[default]
+    Alphabetical,
+    Score,    // Best first, ties closest first (fuzzy lookups), then alphabetical
+    Distance, // Closest first (fuzzy lookups), ties best first
+}
+
+// The orders that make sense for lookups with no word to be close to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOrder {
+    #[default]
+    Alphabetical,
+    Score,
+}
+
+impl From<MatchOrder> for WordOrder {
+    fn from(order: MatchOrder) -> Self {
+        match order {
+            MatchOrder::Alphabetical => WordOrder::Alphabetical,
+            MatchOrder::Score => WordOrder::Score,
+        }
+    }
+}
+
+// A dictionary lookup by pattern. Words are as long as the pattern unless
+// a length range is given; positions past the pattern take any letter.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PatternQuery {
+    pub pattern: String,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub min_score: u8,
+    pub order: MatchOrder,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct WordMatch {
+    pub word: String,
+    pub score: u8,
+    // Edits away from the word looked up (fuzzy lookups)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance: Option<usize>,
+}
+
+// Matching words, and whether that is all of them or `limit` cut it short.
+pub struct WordMatches {
+    pub words: Vec<WordMatch>,
+    pub complete: bool,
+}
+
+pub fn find_words(dawg: &Dawg, query: &PatternQuery) -> Result<WordMatches, PatternError> {
+    let pattern = Pattern::parse(&query.pattern)?;
+    let min_length = query.min_length.unwrap_or(pattern.positions.len());
+    let max_length = query.max_length.unwrap_or(pattern.positions.len().max(min_length));
+    let mut found = Collector::new(query.min_score, query.order.into(), query.limit);
+    if min_length <= max_length {
+        dawg.walk_matching(&pattern.positions, min_length..=max_length, &mut |word, score| found.visit(word, score));
+    }
+    Ok(found.finish())
+}
+
+// Gathers the words of a dictionary walk, which come in alphabetical
+// order, for ranking and limiting.
+pub struct Collector {
+    min_score: u8,
+    order: WordOrder,
+    limit: Option<usize>,
+    words: Vec<WordMatch>,
+}
+
+impl Collector {
+    pub fn new(min_score: u8, order: WordOrder, limit: Option<usize>) -> Self {
+        Collector {
+            min_score,
+            order,
+            limit,
+            words: Vec::new(),
+        }
+    }
+
+    // Keeps a word if it scores high enough. False once the walk can stop.
+    pub fn visit(&mut self, word: &[u8], score: u8) -> bool {
+        self.keep(word, score, None)
+    }
+
+    // Same for a word found `distance` edits away from the one looked up.
+    pub fn visit_near(&mut self, word: &[u8], score: u8, distance: usize) -> bool {
+        self.keep(word, score, Some(distance))
+    }
+
+    fn keep(&mut self, word: &[u8], score: u8, distance: Option<usize>) -> bool {
+        if score >= self.min_score {
+            self.words.push(WordMatch {
+                word: word.iter().map(|&label| (b'A' + label) as char).collect(),
+                score,
+                distance,
+            });
+        }
+        // Alphabetical results come out in order, so one past the limit
+        // is enough to tell whether there are more
+        self.order != WordOrder::Alphabetical || self.limit.is_none_or(|limit| self.words.len() <= limit)
+    }
+
+    pub fn finish(mut self) -> WordMatches {
+        // Stable sorts, so ties stay alphabetical
+        match self.order {
+            WordOrder::Alphabetical => {}
+            WordOrder::Score => self.words.sort_by_key(|w| (Reverse(w.score), w.distance)),
+            WordOrder::Distance => self.words.sort_by_key(|w| (w.distance, Reverse(w.score))),
+        }
+        let complete = self.limit.is_none_or(|limit| self.words.len() <= limit);
+        if let Some(limit) = self.limit {
+            self.words.truncate(limit);
+        }
+        WordMatches {
+            words: self.words,
+            complete,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    // Words of one to six letters over A-F, with random scores.
+    fn scored_words(seed: u64) -> Vec<(String, u8)> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut words: Vec<(String, u8)> = (0..400)
+            .map(|_| {
+                let word = (0..rng.gen_range(1..=6)).map(|_| (b'A' + rng.gen_range(0..6)) as char).collect();
+                (word, rng.gen())
+            })
+            .collect();
+        words.sort();
+        words.dedup_by(|a, b| a.0 == b.0);
+        words
+    }
+
+    fn found(dawg: &Dawg, query: PatternQuery) -> Vec<(String, u8)> {
+        find_words(dawg, &query).unwrap().words.into_iter().map(|w| (w.word, w.score)).collect()
+    }
+
+    #[test]
+    fn finds_the_words_a_pattern_allows() {
+        let words = scored_words(18);
+        let dawg = Dawg::from_scored_words(words.iter().map(|(w, score)| (w, *score)));
+        let not = |letters: &str| ('A'..='Z').filter(|ch| !letters.contains(*ch)).collect::<String>();
+        let any = not("");
+        // Each pattern with the letters it allows at each position
+        let patterns = [
+            ("A?[B-D]", vec!["A".to_string(), any.clone(), "BCD".to_string()]),
+            ("[^AB]?", vec![not("AB"), any.clone()]),
+            ("[a-cF]?e", vec!["ABCF".to_string(), any.clone(), "E".to_string()]),
+            ("?[^A-E]", vec![any.clone(), not("ABCDE")]),
+            ("[B-BD-E]", vec!["BDE".to_string()]),
+            ("", vec![]),
+        ];
+        let mut total = 0;
+        for (pattern, allowed) in patterns {
+            for (min_length, max_length) in [(None, None), (Some(2), Some(5)), (Some(4), None), (Some(3), Some(2))] {
+                let min = min_length.unwrap_or(allowed.len());
+                let max = max_length.unwrap_or(allowed.len().max(min));
+                let expected: Vec<(String, u8)> = words
+                    .iter()
+                    .filter(|(w, _)| (min..=max).contains(&w.len()))
+                    .filter(|(w, _)| w.chars().zip(&allowed).all(|(ch, letters)| letters.contains(ch)))
+                    .cloned()
+                    .collect();
+                let query = PatternQuery {
+                    pattern: pattern.to_string(),
+                    min_length,
+                    max_length,
+                    ..PatternQuery::default()
+                };
+                assert_eq!(found(&dawg, query.clone()), expected, "{} {:?}", pattern, (min_length, max_length));
+                total += expected.len();
+
+                // Best first, ties alphabetical; a limit keeps the first few
+                let mut best = expected.clone();
+                best.sort_by_key(|(_, score)| Reverse(*score));
+                let kept: Vec<(String, u8)> = best.iter().filter(|(_, score)| *score >= 100).take(5).cloned().collect();
+                let query = PatternQuery {
+                    min_score: 100,
+                    order: MatchOrder::Score,
+                    limit: Some(5),
+                    ..query
+                };
+                assert_eq!(found(&dawg, query.clone()), kept);
+                let complete = find_words(&dawg, &query).unwrap().complete;
+                assert_eq!(complete, best.iter().filter(|(_, score)| *score >= 100).count() <= 5);
+            }
+        }
+        assert!(total > 100);
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        let error = |pattern: &str| Pattern::parse(pattern).err();
+        assert_eq!(error("A[BC"), Some(PatternError::UnclosedClass { at: 1 }));
+        assert_eq!(error("[]"), Some(PatternError::EmptyClass { at: 0 }));
+        assert_eq!(error("[^A-Z]"), Some(PatternError::EmptyClass { at: 0 }));
+        assert_eq!(error("A1"), Some(PatternError::UnexpectedChar { at: 1, ch: '1' }));
+        assert_eq!(error("[A-]"), Some(PatternError::UnexpectedChar { at: 2, ch: '-' }));
+    }
+
+    #[test]
+    fn pattern_queries_have_no_distance_order() {
+        let query = |json: &str| serde_json::from_str::<PatternQuery>(json).map(|query| query.order).ok();
+        assert_eq!(query(r#"{ "pattern": "A?" }"#), Some(MatchOrder::Alphabetical));
+        assert_eq!(query(r#"{ "pattern": "A?", "order": "score" }"#), Some(MatchOrder::Score));
+        assert_eq!(query(r#"{ "pattern": "A?", "order": "distance" }"#), None);
+    }
+}
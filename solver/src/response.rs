@@ -0,0 +1,634 @@
+use crate::diagnose::{Conflict, Crossing, Diagnosis, SlotRef};
+use crate::grid::{ConfigError, Grid, GridConfig};
+use crate::layout::{LayoutError, Rejections};
+use crate::rules::{LayoutStats, Violation};
+use crate::pattern::{PatternError, WordMatch, WordMatches};
+use crate::solver::{
+    Enumeration, FillQuality, Limit, SearchStatus, SlotEntry, SlotOption, SlotOptions, SolveOutcome, Solver, SolverStats,
+};
+use crate::theme::{ThemePlacement, Unplaced};
+use serde::Serialize;
+
+// DTOs for the JSON the WASM API hands back to JS.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SolveStatus {
+    Success,
+    Failed, // The request was fine but no fill exists
+    GaveUp, // A budget ran out or the solve was cancelled
+    Error,  // The request itself was invalid
+    Running, // Step-wise search that hasn't finished yet
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    BadJson,
+    GridTooLarge,
+    OutOfBounds,
+    InvalidFixedLetter,
+    Unsatisfiable,
+    Timeout,
+    NodeLimit,
+    BacktrackLimit,
+    Cancelled,
+    NoSearch, // Stepping without a started search
+    NoSuchSlot,
+    BadPattern,
+    UnsupportedSymmetry,
+    EmptyGrid,
+    TooSmall, // No room for an entry
+    InvalidDensity,
+    NoLayout, // Generation budgets ran out
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SolveError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SolveResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<Vec<Vec<char>>>,
+    pub slots: Vec<SlotEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<FillQuality>,
+    pub stats: SolverStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub placed: Option<Vec<ThemePlacement>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unplaced: Option<Vec<Unplaced>>,
+    // Seed to replay a randomized search with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl SolveResponse {
+    pub fn success(solution: &Grid, slots: Vec<SlotEntry>, stats: SolverStats) -> Self {
+        SolveResponse {
+            status: SolveStatus::Success,
+            error: None,
+            grid: Some(render_grid(solution)),
+            quality: Some(FillQuality::from_entries(&slots)),
+            slots,
+            stats,
+            placed: None,
+            unplaced: None,
+            seed: None,
+        }
+    }
+
+    pub fn from_outcome(outcome: &SolveOutcome, slots: Vec<SlotEntry>, stats: SolverStats) -> Self {
+        match outcome {
+            SolveOutcome::Solved(solution) => Self::success(solution, slots, stats),
+            SolveOutcome::Unsatisfiable => Self::failed(ErrorKind::Unsatisfiable, "No solution found", stats),
+            SolveOutcome::GaveUp { reason, best_partial } => {
+                // Hand back the best partial fill, undecided cells blank
+                let mut response = Self::success(best_partial, slots, stats);
+                response.status = SolveStatus::GaveUp;
+                response.error = Some(SolveError::limit(*reason));
+                response
+            }
+        }
+    }
+
+    pub fn failed(kind: ErrorKind, message: &str, stats: SolverStats) -> Self {
+        SolveResponse {
+            status: SolveStatus::Failed,
+            error: Some(SolveError { kind, message: message.to_string() }),
+            grid: None,
+            slots: Vec::new(),
+            quality: None,
+            stats,
+            placed: None,
+            unplaced: None,
+            seed: None,
+        }
+    }
+
+    pub fn error(kind: ErrorKind, message: String) -> Self {
+        SolveResponse {
+            status: SolveStatus::Error,
+            error: Some(SolveError { kind, message }),
+            grid: None,
+            slots: Vec::new(),
+            quality: None,
+            stats: SolverStats::default(),
+            placed: None,
+            unplaced: None,
+            seed: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Plain data with string keys; serialization can't fail
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for SolveResponse {
+    fn from(e: SolveError) -> Self {
+        Self::error(e.kind, e.message)
+    }
+}
+
+impl SolveError {
+    pub fn limit(reason: Limit) -> Self {
+        let (kind, message) = match reason {
+            Limit::Nodes => (ErrorKind::NodeLimit, "Node limit reached"),
+            Limit::Backtracks => (ErrorKind::BacktrackLimit, "Backtrack limit reached"),
+            Limit::Time => (ErrorKind::Timeout, "Time limit reached"),
+            Limit::Cancelled => (ErrorKind::Cancelled, "Solve was cancelled"),
+        };
+        SolveError {
+            kind,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn bad_json(what: &str, e: serde_json::Error) -> Self {
+        SolveError {
+            kind: ErrorKind::BadJson,
+            message: format!("Invalid {}: {}", what, e),
+        }
+    }
+}
+
+impl From<PatternError> for SolveError {
+    fn from(e: PatternError) -> Self {
+        SolveError {
+            kind: ErrorKind::BadPattern,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<LayoutError> for SolveError {
+    fn from(e: LayoutError) -> Self {
+        let kind = match e {
+            LayoutError::UnsupportedSymmetry { .. } => ErrorKind::UnsupportedSymmetry,
+            LayoutError::EmptyGrid { .. } => ErrorKind::EmptyGrid,
+            LayoutError::TooSmall { .. } => ErrorKind::TooSmall,
+            LayoutError::InvalidDensity(_) => ErrorKind::InvalidDensity,
+            LayoutError::NoLayout { .. } | LayoutError::Unfillable { .. } => ErrorKind::NoLayout,
+            LayoutError::TimedOut { .. } => ErrorKind::Timeout,
+            LayoutError::Cancelled { .. } => ErrorKind::Cancelled,
+        };
+        SolveError {
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<ConfigError> for SolveError {
+    fn from(e: ConfigError) -> Self {
+        let kind = match e {
+            ConfigError::TooLarge { .. } => ErrorKind::GridTooLarge,
+            ConfigError::OutOfBounds { .. } => ErrorKind::OutOfBounds,
+            ConfigError::InvalidFixedLetter { .. } => ErrorKind::InvalidFixedLetter,
+        };
+        SolveError {
+            kind,
+            message: e.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Fill {
+    pub grid: Vec<Vec<char>>,
+    pub slots: Vec<SlotEntry>,
+    pub quality: FillQuality,
+}
+
+// Result of counting fills or asking for the best few.
+#[derive(Clone, Debug, Serialize)]
+pub struct EnumerateResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub count: usize,
+    pub complete: bool, // Every fill was found, so `count` is exact
+    pub fills: Vec<Fill>,
+    pub stats: SolverStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl EnumerateResponse {
+    pub fn new(solver: &Solver, found: &Enumeration, stats: SolverStats) -> Self {
+        let (status, error) = match found.stopped {
+            Some(reason) => (SolveStatus::GaveUp, Some(SolveError::limit(reason))),
+            None if found.count == 0 => (
+                SolveStatus::Failed,
+                Some(SolveError {
+                    kind: ErrorKind::Unsatisfiable,
+                    message: "No solution found".to_string(),
+                }),
+            ),
+            None => (SolveStatus::Success, None),
+        };
+        let fills = found
+            .best
+            .iter()
+            .map(|grid| {
+                let slots = solver.slot_entries(grid);
+                Fill {
+                    grid: render_grid(grid),
+                    quality: FillQuality::from_entries(&slots),
+                    slots,
+                }
+            })
+            .collect();
+        EnumerateResponse {
+            status,
+            error,
+            count: found.count,
+            complete: found.complete,
+            fills,
+            stats,
+            seed: solver.seed(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for EnumerateResponse {
+    fn from(e: SolveError) -> Self {
+        EnumerateResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            count: 0,
+            complete: false,
+            fills: Vec::new(),
+            stats: SolverStats::default(),
+            seed: None,
+        }
+    }
+}
+
+// One solve of a heuristic comparison. Which heuristics it used is in
+// its stats.
+#[derive(Clone, Debug, Serialize)]
+pub struct HeuristicRun {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<FillQuality>,
+    pub stats: SolverStats,
+}
+
+impl From<SolveResponse> for HeuristicRun {
+    fn from(response: SolveResponse) -> Self {
+        HeuristicRun {
+            status: response.status,
+            error: response.error,
+            quality: response.quality,
+            stats: response.stats,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CompareResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub runs: Vec<HeuristicRun>,
+}
+
+impl CompareResponse {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for CompareResponse {
+    fn from(e: SolveError) -> Self {
+        CompareResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            runs: Vec::new(),
+        }
+    }
+}
+
+// Where a step-wise search stands after a call to `step`.
+#[derive(Clone, Debug, Serialize)]
+pub struct StepResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub depth: usize, // Open decisions
+    // Letters each cell can still take, "#" for black cells
+    pub domains: Vec<Vec<String>>,
+    pub slots: Vec<SlotEntry>, // Slots filled so far
+    pub stats: SolverStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+impl StepResponse {
+    pub fn new(solver: &Solver, status: SearchStatus) -> Self {
+        let (status, error) = report_status(status);
+        StepResponse {
+            status,
+            error,
+            depth: solver.depth(),
+            domains: render_domains(solver.grid()),
+            slots: solver.slot_entries(solver.grid()),
+            stats: solver.stats().clone(),
+            seed: solver.seed(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+// Candidate words for one slot, best first.
+#[derive(Clone, Debug, Serialize)]
+pub struct SuggestResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot: Option<usize>,
+    pub words: Vec<SlotOption>,
+    pub complete: bool, // Every candidate was checked
+    pub stats: SolverStats,
+}
+
+impl SuggestResponse {
+    pub fn new(slot: usize, found: Option<SlotOptions>, limit: Option<usize>, stats: SolverStats) -> Self {
+        let Some(mut found) = found else {
+            let mut response = Self::from(SolveError {
+                kind: ErrorKind::Unsatisfiable,
+                message: "The grid has no consistent fill".to_string(),
+            });
+            response.status = SolveStatus::Failed;
+            response.slot = Some(slot);
+            response.stats = stats;
+            return response;
+        };
+        if let Some(limit) = limit {
+            found.words.truncate(limit);
+        }
+        SuggestResponse {
+            status: if found.complete { SolveStatus::Success } else { SolveStatus::GaveUp },
+            error: found.stopped.map(SolveError::limit),
+            slot: Some(slot),
+            words: found.words,
+            complete: found.complete,
+            stats,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for SuggestResponse {
+    fn from(e: SolveError) -> Self {
+        SuggestResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            slot: None,
+            words: Vec::new(),
+            complete: false,
+            stats: SolverStats::default(),
+        }
+    }
+}
+
+// A generated layout: the grid config's fields alongside the status.
+#[derive(Serialize)]
+pub struct LayoutResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    #[serde(flatten)]
+    pub layout: Option<GridConfig>,
+    // Layouts generated with a fill only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill: Option<SolveResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejections: Option<Rejections>,
+}
+
+impl LayoutResponse {
+    pub fn success(layout: GridConfig) -> Self {
+        LayoutResponse {
+            status: SolveStatus::Success,
+            error: None,
+            layout: Some(layout),
+            fill: None,
+            rejections: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for LayoutResponse {
+    fn from(e: SolveError) -> Self {
+        LayoutResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            layout: None,
+            fill: None,
+            rejections: None,
+        }
+    }
+}
+
+// How a hand-drawn layout measures up: every rule it breaks, and its
+// figures. `status` is `success` whenever the layout could be read.
+#[derive(Clone, Debug, Serialize)]
+pub struct ValidateResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub valid: bool,
+    pub violations: Vec<Violation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<LayoutStats>,
+}
+
+impl ValidateResponse {
+    pub fn new(violations: Vec<Violation>, stats: LayoutStats) -> Self {
+        ValidateResponse {
+            status: SolveStatus::Success,
+            error: None,
+            valid: violations.is_empty(),
+            violations,
+            stats: Some(stats),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for ValidateResponse {
+    fn from(e: SolveError) -> Self {
+        ValidateResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            valid: false,
+            violations: Vec::new(),
+            stats: None,
+        }
+    }
+}
+
+// Result of a dictionary lookup.
+#[derive(Clone, Debug, Serialize)]
+pub struct WordsResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub words: Vec<WordMatch>,
+    pub complete: bool, // False if the limit left matches out
+}
+
+impl WordsResponse {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<WordMatches> for WordsResponse {
+    fn from(found: WordMatches) -> Self {
+        WordsResponse {
+            status: SolveStatus::Success,
+            error: None,
+            words: found.words,
+            complete: found.complete,
+        }
+    }
+}
+
+impl From<SolveError> for WordsResponse {
+    fn from(e: SolveError) -> Self {
+        WordsResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            words: Vec::new(),
+            complete: false,
+        }
+    }
+}
+
+// What a diagnosis found. `status` is that of solving the whole grid:
+// `failed` when the rest of the report explains why it has no fill.
+#[derive(Clone, Debug, Serialize)]
+pub struct DiagnoseResponse {
+    pub status: SolveStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<SolveError>,
+    pub empty_slots: Vec<SlotRef>,
+    pub crossings: Vec<Crossing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conflict: Option<Conflict>,
+    pub stats: SolverStats,
+}
+
+impl DiagnoseResponse {
+    pub fn new(diagnosis: Diagnosis) -> Self {
+        let (status, error) = report_status(diagnosis.status);
+        DiagnoseResponse {
+            status,
+            error,
+            empty_slots: diagnosis.empty_slots,
+            crossings: diagnosis.crossings,
+            conflict: diagnosis.conflict,
+            stats: diagnosis.stats,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<SolveError> for DiagnoseResponse {
+    fn from(e: SolveError) -> Self {
+        DiagnoseResponse {
+            status: SolveStatus::Error,
+            error: Some(e),
+            empty_slots: Vec::new(),
+            crossings: Vec::new(),
+            conflict: None,
+            stats: SolverStats::default(),
+        }
+    }
+}
+
+fn report_status(status: SearchStatus) -> (SolveStatus, Option<SolveError>) {
+    match status {
+        SearchStatus::Running => (SolveStatus::Running, None),
+        SearchStatus::Solved => (SolveStatus::Success, None),
+        SearchStatus::Unsatisfiable => (
+            SolveStatus::Failed,
+            Some(SolveError {
+                kind: ErrorKind::Unsatisfiable,
+                message: "No solution found".to_string(),
+            }),
+        ),
+        SearchStatus::GaveUp(reason) => (SolveStatus::GaveUp, Some(SolveError::limit(reason))),
+    }
+}
+
+pub fn render_domains(grid: &Grid) -> Vec<Vec<String>> {
+    (0..grid.height)
+        .map(|r| {
+            (0..grid.width)
+                .map(|c| {
+                    let cell = grid.get_cell(r, c);
+                    if cell.is_black {
+                        "#".to_string()
+                    } else {
+                        cell.domain.iter().collect()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Convert a solution to a simplified format for JS
+pub fn render_grid(solution: &Grid) -> Vec<Vec<char>> {
+    let mut output_grid = vec![vec![' '; solution.width]; solution.height];
+    for (r, row) in output_grid.iter_mut().enumerate() {
+        for (c, out) in row.iter_mut().enumerate() {
+            let cell = solution.get_cell(r, c);
+            if !cell.is_black {
+                if let Some(ch) = cell.domain.is_singleton() {
+                    *out = ch;
+                } else {
+                    *out = ' '; // Undecided (partial fills only)
+                }
+            } else {
+                *out = '#';
+            }
+        }
+    }
+    output_grid
+}
@@ -0,0 +1,475 @@
+use crate::grid::Grid;
+use crate::layout::Symmetry;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, VecDeque};
+
+// What makes a layout acceptable. Each rule can be switched off: the
+// optional ones with null, the others with false. The defaults are the
+// checks the generator has always made.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LayoutRules {
+    pub connected: bool, // Every white square reachable from every other
+    pub min_entry_length: Option<usize>,
+    pub max_entry_length: Option<usize>,
+    pub max_words: Option<usize>,
+    pub max_black_ratio: Option<f64>, // Black squares over all squares, 0 to 1
+    pub no_black_blocks: bool,        // No 2x2 block of black squares
+    pub no_unchecked: bool,           // Every white square in both an across and a down entry
+    pub no_cheaters: bool,            // Every black square adds to the word count
+    pub max_edge_run: Option<usize>,  // Black squares in a row along an edge
+}
+
+impl Default for LayoutRules {
+    fn default() -> Self {
+        LayoutRules {
+            connected: true,
+            min_entry_length: Some(3),
+            max_entry_length: None,
+            max_words: None,
+            max_black_ratio: None,
+            no_black_blocks: false,
+            no_unchecked: true,
+            no_cheaters: false,
+            max_edge_run: None,
+        }
+    }
+}
+
+// Named like the `LayoutRules` field that switches the rule on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    Connected,
+    MinEntryLength,
+    MaxEntryLength,
+    MaxWords,
+    MaxBlackRatio,
+    NoBlackBlocks,
+    NoUnchecked,
+    NoCheaters,
+    MaxEdgeRun,
+}
+
+// One place a layout breaks a rule, with the squares involved (none for
+// rules about the grid as a whole).
+#[derive(Clone, Debug, Serialize)]
+pub struct Violation {
+    pub rule: Rule,
+    pub message: String,
+    pub cells: Vec<(usize, usize)>,
+}
+
+// Figures for a layout, for a constructor to keep an eye on while drawing.
+#[derive(Clone, Debug, Serialize)]
+pub struct LayoutStats {
+    pub words: usize,
+    pub black_squares: usize,
+    pub average_word_length: f64,
+    pub length_histogram: BTreeMap<usize, usize>, // Entries of each length
+    pub open_squares: usize,                      // White squares with no black square around them
+    pub open_blocks: Vec<Vec<(usize, usize)>>,    // Open squares grouped by which touch
+    pub symmetries: Vec<Symmetry>,                // Empty if the layout has none
+}
+
+impl LayoutStats {
+    pub fn of(grid: &Grid) -> Self {
+        let entries: Vec<Run> = runs(grid).into_iter().filter(|run| run.length > 1).collect();
+        let letters: usize = entries.iter().map(|run| run.length).sum();
+        let mut length_histogram = BTreeMap::new();
+        for run in &entries {
+            *length_histogram.entry(run.length).or_insert(0) += 1;
+        }
+
+        // Open as in the usual sense: no black square in any of the eight
+        // directions. The grid's edge doesn't count against it.
+        let open = |r: usize, c: usize| {
+            (r.saturating_sub(1)..(r + 2).min(grid.height))
+                .all(|r| (c.saturating_sub(1)..(c + 2).min(grid.width)).all(|c| !grid.get_cell(r, c).is_black))
+        };
+        let open_blocks = regions(grid, open);
+
+        LayoutStats {
+            words: entries.len(),
+            black_squares: grid.cells.iter().filter(|cell| cell.is_black).count(),
+            average_word_length: if entries.is_empty() { 0.0 } else { letters as f64 / entries.len() as f64 },
+            length_histogram,
+            open_squares: open_blocks.iter().map(Vec::len).sum(),
+            open_blocks,
+            symmetries: Symmetry::MIRRORED.into_iter().filter(|symmetry| symmetry.holds(grid)).collect(),
+        }
+    }
+}
+
+// A maximal line of white squares. Runs of two or more are the entries.
+#[derive(Clone, Copy, Debug)]
+pub struct Run {
+    pub r: usize,
+    pub c: usize,
+    pub length: usize,
+    pub is_across: bool,
+}
+
+impl Run {
+    pub fn cells(&self) -> Vec<(usize, usize)> {
+        (0..self.length)
+            .map(|i| if self.is_across { (self.r, self.c + i) } else { (self.r + i, self.c) })
+            .collect()
+    }
+
+    fn describe(&self) -> String {
+        let direction = if self.is_across { "across" } else { "down" };
+        format!("{} entry at ({}, {})", direction, self.r, self.c)
+    }
+}
+
+// Every run in the grid, across runs first, each in reading order.
+pub fn runs(grid: &Grid) -> Vec<Run> {
+    let mut runs = Vec::new();
+    for is_across in [true, false] {
+        let (lines, along) = if is_across { (grid.height, grid.width) } else { (grid.width, grid.height) };
+        for line in 0..lines {
+            let mut start = 0;
+            for i in 0..=along {
+                let at = if is_across { (line, i) } else { (i, line) };
+                if i < along && !grid.get_cell(at.0, at.1).is_black {
+                    continue;
+                }
+                if i > start {
+                    let (r, c) = if is_across { (line, start) } else { (start, line) };
+                    runs.push(Run {
+                        r,
+                        c,
+                        length: i - start,
+                        is_across,
+                    });
+                }
+                start = i + 1;
+            }
+        }
+    }
+    runs
+}
+
+impl LayoutRules {
+    // Everything wrong with `grid`, rule by rule.
+    pub fn check(&self, grid: &Grid) -> Vec<Violation> {
+        let runs = runs(grid);
+        let mut violations = Vec::new();
+
+        if self.connected {
+            let cut_off = cut_off_cells(grid);
+            if !cut_off.is_empty() {
+                violations.push(Violation {
+                    rule: Rule::Connected,
+                    message: format!("{} white squares are cut off from the rest", cut_off.len()),
+                    cells: cut_off,
+                });
+            }
+        }
+
+        for run in &runs {
+            if run.length == 1 {
+                if self.no_unchecked {
+                    let direction = if run.is_across { "across" } else { "down" };
+                    violations.push(Violation {
+                        rule: Rule::NoUnchecked,
+                        message: format!("({}, {}) is in no {} entry", run.r, run.c, direction),
+                        cells: run.cells(),
+                    });
+                }
+                continue;
+            }
+            if let Some(min) = self.min_entry_length.filter(|&min| run.length < min) {
+                violations.push(Violation {
+                    rule: Rule::MinEntryLength,
+                    message: format!("{} has {} letters, fewer than {}", run.describe(), run.length, min),
+                    cells: run.cells(),
+                });
+            }
+            if let Some(max) = self.max_entry_length.filter(|&max| run.length > max) {
+                violations.push(Violation {
+                    rule: Rule::MaxEntryLength,
+                    message: format!("{} has {} letters, more than {}", run.describe(), run.length, max),
+                    cells: run.cells(),
+                });
+            }
+        }
+
+        let words = runs.iter().filter(|run| run.length > 1).count();
+        if let Some(max) = self.max_words.filter(|&max| words > max) {
+            violations.push(Violation {
+                rule: Rule::MaxWords,
+                message: format!("{} words, more than {}", words, max),
+                cells: vec![],
+            });
+        }
+
+        let black = grid.cells.iter().filter(|cell| cell.is_black).count();
+        let ratio = black as f64 / grid.cells.len().max(1) as f64;
+        if let Some(max) = self.max_black_ratio.filter(|&max| ratio > max) {
+            violations.push(Violation {
+                rule: Rule::MaxBlackRatio,
+                message: format!("{:.1}% of squares are black, more than {:.1}%", ratio * 100.0, max * 100.0),
+                cells: vec![],
+            });
+        }
+
+        if self.no_black_blocks {
+            for r in 1..grid.height {
+                for c in 1..grid.width {
+                    let block = [(r - 1, c - 1), (r - 1, c), (r, c - 1), (r, c)];
+                    if block.iter().all(|&(r, c)| grid.get_cell(r, c).is_black) {
+                        violations.push(Violation {
+                            rule: Rule::NoBlackBlocks,
+                            message: format!("2x2 block of black squares at ({}, {})", r - 1, c - 1),
+                            cells: block.to_vec(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if self.no_cheaters {
+            for r in 0..grid.height {
+                for c in 0..grid.width {
+                    if grid.get_cell(r, c).is_black && is_cheater(grid, r, c) {
+                        violations.push(Violation {
+                            rule: Rule::NoCheaters,
+                            message: format!("black square at ({}, {}) doesn't add to the word count", r, c),
+                            cells: vec![(r, c)],
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(max) = self.max_edge_run {
+            let (w, h) = (grid.width, grid.height);
+            let edges: [(&str, Vec<(usize, usize)>); 4] = [
+                ("top", (0..w).map(|c| (0, c)).collect()),
+                ("bottom", (0..w).map(|c| (h.saturating_sub(1), c)).collect()),
+                ("left", (0..h).map(|r| (r, 0)).collect()),
+                ("right", (0..h).map(|r| (r, w.saturating_sub(1))).collect()),
+            ];
+            for (edge, cells) in edges {
+                for run in cells.split(|&(r, c)| !grid.get_cell(r, c).is_black) {
+                    if run.len() > max {
+                        violations.push(Violation {
+                            rule: Rule::MaxEdgeRun,
+                            message: format!(
+                                "{} black squares in a row along the {} edge, more than {}",
+                                run.len(),
+                                edge,
+                                max
+                            ),
+                            cells: run.to_vec(),
+                        });
+                    }
+                }
+            }
+        }
+
+        violations
+    }
+}
+
+// White squares outside the largest connected region.
+fn cut_off_cells(grid: &Grid) -> Vec<(usize, usize)> {
+    let mut regions = regions(grid, |r, c| !grid.get_cell(r, c).is_black);
+    let Some(largest) = (0..regions.len()).max_by_key(|&i| (regions[i].len(), Reverse(i))) else {
+        return vec![];
+    };
+    regions.remove(largest);
+    let mut cells: Vec<(usize, usize)> = regions.into_iter().flatten().collect();
+    cells.sort_unstable();
+    cells
+}
+
+// Groups of squares that pass `member` and touch side by side, in reading
+// order of their first square.
+fn regions(grid: &Grid, member: impl Fn(usize, usize) -> bool) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = vec![false; grid.cells.len()];
+    let mut regions = Vec::new();
+    for start in 0..grid.cells.len() {
+        if seen[start] || !member(start / grid.width, start % grid.width) {
+            continue;
+        }
+        let mut region = Vec::new();
+        let mut queue = VecDeque::new();
+        seen[start] = true;
+        queue.push_back(start);
+        while let Some(idx) = queue.pop_front() {
+            let (r, c) = (idx / grid.width, idx % grid.width);
+            region.push((r, c));
+            let neighbours = [
+                (r > 0).then(|| idx - grid.width),
+                (r + 1 < grid.height).then(|| idx + grid.width),
+                (c > 0).then(|| idx - 1),
+                (c + 1 < grid.width).then(|| idx + 1),
+            ];
+            for next in neighbours.into_iter().flatten() {
+                if !seen[next] && member(next / grid.width, next % grid.width) {
+                    seen[next] = true;
+                    queue.push_back(next);
+                }
+            }
+        }
+        region.sort_unstable();
+        regions.push(region);
+    }
+    regions
+}
+
+// A black square is a cheater if turning it white would leave the word
+// count as it is, e.g. one tucked into the corner of a larger black area.
+fn is_cheater(grid: &Grid, r: usize, c: usize) -> bool {
+    let white = |r: usize, c: usize| !grid.get_cell(r, c).is_black;
+    let left = (0..c).rev().take_while(|&c| white(r, c)).count();
+    let right = (c + 1..grid.width).take_while(|&c| white(r, c)).count();
+    let up = (0..r).rev().take_while(|&r| white(r, c)).count();
+    let down = (r + 1..grid.height).take_while(|&r| white(r, c)).count();
+    words_gained(left, right) + words_gained(up, down) == 0
+}
+
+// Change in entries on a line when the black square between runs of
+// `before` and `after` white squares turns white.
+fn words_gained(before: usize, after: usize) -> i32 {
+    let entry = |length: usize| (length > 1) as i32;
+    entry(before + after + 1) - entry(before) - entry(after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // '#' for black squares, anything else for white ones.
+    fn grid(rows: &[&str]) -> Grid {
+        let mut grid = Grid::new(rows[0].len(), rows.len());
+        for (r, row) in rows.iter().enumerate() {
+            for (c, square) in row.chars().enumerate() {
+                grid.set_black(r, c, square == '#');
+            }
+        }
+        grid
+    }
+
+    fn no_rules() -> LayoutRules {
+        LayoutRules {
+            connected: false,
+            min_entry_length: None,
+            max_entry_length: None,
+            max_words: None,
+            max_black_ratio: None,
+            no_black_blocks: false,
+            no_unchecked: false,
+            no_cheaters: false,
+            max_edge_run: None,
+        }
+    }
+
+    #[test]
+    fn each_rule_fires_on_its_own() {
+        let open = grid(&[".....", ".....", ".....", ".....", "....."]);
+        let cases = [
+            (
+                Rule::Connected,
+                LayoutRules { connected: true, ..no_rules() },
+                grid(&["..#..", "..#..", "..#..", "..#..", "..#.."]),
+            ),
+            (
+                Rule::MinEntryLength,
+                LayoutRules { min_entry_length: Some(3), ..no_rules() },
+                grid(&[".....", "..#..", ".....", ".....", "....."]),
+            ),
+            (Rule::MaxEntryLength, LayoutRules { max_entry_length: Some(4), ..no_rules() }, open.clone()),
+            (Rule::MaxWords, LayoutRules { max_words: Some(9), ..no_rules() }, open.clone()),
+            (
+                Rule::MaxBlackRatio,
+                LayoutRules { max_black_ratio: Some(0.1), ..no_rules() },
+                grid(&["#....", ".....", "..#..", ".....", "....#"]),
+            ),
+            (
+                Rule::NoBlackBlocks,
+                LayoutRules { no_black_blocks: true, ..no_rules() },
+                grid(&["##...", "##...", ".....", "...##", "...##"]),
+            ),
+            (Rule::NoUnchecked, LayoutRules { no_unchecked: true, ..no_rules() }, grid(&["...", "#.#", "..."])),
+            (
+                Rule::NoCheaters,
+                LayoutRules { no_cheaters: true, ..no_rules() },
+                grid(&["##...", "#....", ".....", ".....", "....."]),
+            ),
+            (
+                Rule::MaxEdgeRun,
+                LayoutRules { max_edge_run: Some(1), ..no_rules() },
+                grid(&["##...", ".....", ".....", ".....", "....."]),
+            ),
+        ];
+        for (rule, rules, broken) in cases {
+            let violations = rules.check(&broken);
+            assert!(!violations.is_empty(), "{:?}", rule);
+            assert!(violations.iter().all(|violation| violation.rule == rule), "{:?}", rule);
+            assert!(rules.check(&grid(&["...", "...", "..."])).is_empty(), "{:?}", rule);
+        }
+        assert!(no_rules().check(&grid(&["##...", "##...", ".#...", "...##", "...##"])).is_empty());
+    }
+
+    #[test]
+    fn violations_point_at_the_squares_involved() {
+        let rules = LayoutRules::default();
+        let violations = rules.check(&grid(&["..#..", "..#..", "..#..", "..#..", "..#.."]));
+        let cut_off = violations.iter().find(|violation| violation.rule == Rule::Connected).unwrap();
+        assert_eq!(cut_off.cells, (0..5).flat_map(|r| [(r, 3), (r, 4)]).collect::<Vec<_>>());
+
+        let rules = LayoutRules { max_edge_run: Some(2), ..no_rules() };
+        let violations = rules.check(&grid(&["###..", ".....", "#....", "#....", "#...."]));
+        let cells: Vec<_> = violations.iter().map(|violation| violation.cells.clone()).collect();
+        assert_eq!(cells, [vec![(0, 0), (0, 1), (0, 2)], vec![(2, 0), (3, 0), (4, 0)]]);
+    }
+
+    #[test]
+    fn cheaters_add_nothing_to_the_word_count() {
+        let layout = grid(&["##...", "#....", "..#..", ".....", "....."]);
+        assert!(is_cheater(&layout, 0, 0));
+        // One splits an entry in two, the other keeps two squares from
+        // making one
+        assert!(!is_cheater(&layout, 2, 2));
+        assert!(!is_cheater(&grid(&[".#.", "...", "..."]), 0, 1));
+
+        // Turning each white is what decides it
+        for (r, c) in [(0, 0), (0, 1), (1, 0), (2, 2)] {
+            let mut whiter = layout.clone();
+            whiter.set_black(r, c, false);
+            let words = |grid: &Grid| runs(grid).iter().filter(|run| run.length > 1).count();
+            assert_eq!(is_cheater(&layout, r, c), words(&whiter) == words(&layout), "({}, {})", r, c);
+        }
+    }
+
+    #[test]
+    fn stats_describe_the_layout() {
+        let stats = LayoutStats::of(&grid(&[".....", ".....", "..#..", ".....", "....."]));
+        assert_eq!(stats.words, 12);
+        assert_eq!(stats.black_squares, 1);
+        assert_eq!(stats.average_word_length, 4.0);
+        assert_eq!(stats.length_histogram, BTreeMap::from([(2, 4), (5, 8)]));
+        // All but the black square and the eight around it, in one ring
+        assert_eq!(stats.open_squares, 16);
+        assert_eq!(stats.open_blocks.len(), 1);
+        assert_eq!(stats.symmetries, Symmetry::MIRRORED);
+
+        let stats = LayoutStats::of(&grid(&["...#...", "...#...", "...#..."]));
+        assert_eq!(stats.open_blocks, [
+            vec![(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)],
+            vec![(0, 5), (0, 6), (1, 5), (1, 6), (2, 5), (2, 6)],
+        ]);
+        assert_eq!(stats.open_squares, 12);
+        assert_eq!(stats.symmetries, [Symmetry::Rotational180, Symmetry::LeftRight, Symmetry::TopBottom]);
+
+        let stats = LayoutStats::of(&grid(&["#.", ".."]));
+        assert_eq!(stats.symmetries, [Symmetry::Diagonal]);
+        assert_eq!(stats.open_squares, 0);
+        assert_eq!(LayoutStats::of(&grid(&["##", "##"])).average_word_length, 0.0);
+    }
+}
@@ -1,7 +1,227 @@
 use crate::dawg::Dawg;
 use crate::domain::Domain;
 use crate::grid::Grid;
+use crate::layout::random_seed;
+use crate::word_index::{Bitset, WordIndex};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
 use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Duration;
+use web_time::Instant;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueOrder {
+    Alphabetical,
+    #[default]
+    Score, // Letters that lead to the best-scoring words first
+    // Least constraining value: letters (or words) most common at the
+    // crossing positions in the dictionary first, leaving crossings the
+    // most options.
+    Lcv,
+}
+
+// Which open variable the search decides next.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableOrder {
+    #[default]
+    Mrv, // Fewest remaining letters (cells) or candidate words (slots)
+    // Fewest remaining values relative to how often the variable's slots
+    // have caused a dead end so far
+    DomWdeg,
+    // The slot with the fewest candidate words, then its most constrained
+    // cell. Same as Mrv for the word model.
+    SlotMrv,
+}
+
+// What the search branches on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchModel {
+    Cell, // A letter for one cell at a time
+    // A whole word for one slot at a time, with slot candidates filtered
+    // through a positional letter index. Usually much faster on dense or
+    // themed grids, at the cost of building the index per solve.
+    Word,
+}
+
+// When to abandon the current search tree and start over from the top.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Restarts {
+    None,
+    Luby,      // After restart_base x 1, 1, 2, 1, 1, 2, 4, ... backtracks
+    Geometric, // After restart_base x 1.5^n backtracks
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct SolveOptions {
+    pub min_score: u8, // Words scoring below this are never placed
+    pub value_order: ValueOrder,
+    pub variable_order: VariableOrder,
+    // Lets the same word fill more than one slot. Only useful for tiny
+    // grids (word squares); published puzzles never repeat an answer.
+    pub allow_duplicates: bool,
+    // Search budgets. The clock starts when the solver is created.
+    pub max_nodes: Option<u64>,
+    pub max_backtracks: Option<u64>,
+    pub time_limit_ms: Option<u64>,
+    pub model: SearchModel,
+    // On a dead end, jump straight back to the latest decision that
+    // contributed to it instead of the most recent one.
+    pub backjumping: bool,
+    // Remember the decision combinations behind each dead end and fail
+    // as soon as one recurs elsewhere in the tree. Needs backjumping.
+    pub learn_nogoods: bool,
+    // Breaks ties in variable and value order at random, reproducibly.
+    // Chosen at random if restarts are on and no seed is given.
+    pub seed: Option<u64>,
+    // Restarts only pay off with random tie-breaking, and stop once a fill
+    // has been found so enumeration never repeats one.
+    pub restarts: Restarts,
+    pub restart_base: u64,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        SolveOptions {
+            min_score: 0,
+            value_order: ValueOrder::Score,
+            variable_order: VariableOrder::Mrv,
+            allow_duplicates: false,
+            max_nodes: None,
+            max_backtracks: None,
+            time_limit_ms: None,
+            model: SearchModel::Cell,
+            backjumping: true,
+            learn_nogoods: false,
+            seed: None,
+            restarts: Restarts::None,
+            restart_base: 100,
+        }
+    }
+}
+
+// Why a search stopped before proving the grid solvable or not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Limit {
+    Nodes,
+    Backtracks,
+    Time,
+    Cancelled,
+}
+
+// Where a step-wise search stands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchStatus {
+    Running,
+    Solved,
+    Unsatisfiable,
+    GaveUp(Limit),
+}
+
+pub enum SolveOutcome {
+    Solved(Grid),
+    Unsatisfiable,
+    // The most-filled consistent state the search reached
+    GaveUp { reason: Limit, best_partial: Grid },
+}
+
+pub struct Enumeration {
+    pub count: usize,
+    pub best: Vec<Grid>, // Best first
+    pub complete: bool,  // Every fill was found
+    pub stopped: Option<Limit>,
+}
+
+// A word that fits a slot, and what it leaves the slots crossing it.
+#[derive(Clone, Debug, Serialize)]
+pub struct SlotOption {
+    pub word: String,
+    pub score: u8,
+    pub options: u64,  // Candidate words left across the crossing slots
+    pub tightest: u32, // Fewest left for any one crossing slot
+}
+
+pub struct SlotOptions {
+    pub words: Vec<SlotOption>,
+    pub complete: bool, // Every candidate was checked
+    pub stopped: Option<Limit>,
+}
+
+// Names a slot by id, or by one of its cells and its direction.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SlotLocator {
+    Id { slot: usize },
+    Cell { r: usize, c: usize, is_across: bool },
+}
+
+// A filled slot as reported to the client.
+#[derive(Clone, Debug, Serialize)]
+pub struct SlotEntry {
+    pub id: usize,
+    pub r: usize,
+    pub c: usize,
+    pub length: usize,
+    pub is_across: bool,
+    pub word: String,
+    pub score: u8,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SolverStats {
+    pub nodes: u64,        // Values tried
+    pub backtracks: u64,   // Values that failed
+    pub propagations: u64, // Slot revisions during propagation
+    pub backjumps: u64,    // Decisions skipped by backjumping
+    pub nogoods: u64,      // Nogoods learned
+    pub restarts: u64,
+    pub max_depth: u64, // Most decisions open at once
+    pub elapsed_ms: f64,
+    // The heuristics behind these numbers, for comparing runs
+    pub variable_order: VariableOrder,
+    pub value_order: ValueOrder,
+}
+
+impl SolverStats {
+    pub fn merge(&mut self, other: &SolverStats) {
+        self.nodes += other.nodes;
+        self.backtracks += other.backtracks;
+        self.propagations += other.propagations;
+        self.backjumps += other.backjumps;
+        self.nogoods += other.nogoods;
+        self.restarts += other.restarts;
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.elapsed_ms += other.elapsed_ms;
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FillQuality {
+    pub average: f64,
+    pub min: u8,
+}
+
+impl FillQuality {
+    pub fn from_entries(entries: &[SlotEntry]) -> Self {
+        if entries.is_empty() {
+            return FillQuality { average: 0.0, min: 0 };
+        }
+        let total: u32 = entries.iter().map(|e| e.score as u32).sum();
+        FillQuality {
+            average: total as f64 / entries.len() as f64,
+            min: entries.iter().map(|e| e.score).min().unwrap_or(0),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Slot {
@@ -13,23 +233,233 @@ pub struct Slot {
     pub cells: Vec<(usize, usize)>, // Coordinates of cells in this slot
 }
 
+#[derive(Clone, Copy, Debug)]
+enum Var {
+    Cell(usize), // Values are letters (0 = A)
+    Slot(usize), // Values are word ids in the slot's length index
+}
+
+// An open decision: the variable being tried, its values in search order,
+// and the trail length before any of them was assigned.
+#[derive(Clone)]
+struct Frame {
+    var: Var,
+    values: Vec<usize>,
+    next: usize,
+    trail_len: usize,
+    // Earlier decision levels behind this variable's pruned and failed
+    // values (backjumping only)
+    conflict: Bitset,
+}
+
+// A change to undo on backtrack: the cell's domain, and its reason when
+// backjumping, as they were before.
+#[derive(Clone)]
+struct TrailEntry {
+    cell_idx: usize,
+    domain: Domain,
+    reason: Option<Bitset>,
+}
+
+// Longest nogood worth keeping, and how many to keep at most
+const MAX_NOGOOD_LEN: usize = 8;
+const MAX_NOGOODS: usize = 2_000;
+
+#[derive(Clone)]
 pub struct Solver {
     grid: Grid,
-    dawg: Dawg,
+    dawg: Rc<Dawg>,
+    // Extra words any open slot may also take (theme words still waiting to
+    // be placed). Never consulted unless set.
+    extra: Option<Rc<Dawg>>,
+    options: SolveOptions,
     slots: Vec<Slot>,
     // Map from (r, c) to list of slot indices that pass through this cell
     cell_to_slots: Vec<Vec<usize>>, 
+    // Slot indices grouped by length, for the all-different constraint
+    slots_by_length: Vec<Vec<usize>>,
+    // Slots pinned to a word outside the dictionary check, with its score
+    locked: Vec<Option<(Vec<u8>, u8)>>,
+    // Slots left out of propagation and search altogether
+    ignored: Vec<bool>,
+    stats: SolverStats,
+    deadline: Option<Instant>,
+    // Polled once per node; returning true abandons the search
+    cancel: Option<Rc<dyn Fn() -> bool>>,
+    best_partial: Option<(usize, Grid)>,
+    // Built when a search that needs it starts (word model, slot MRV, LCV)
+    index: Option<Rc<WordIndex>>,
+    // Per slot, 1 + the dead ends its revision has hit (dom/wdeg)
+    weights: Vec<u32>,
+    // Open decisions, innermost last
+    stack: Vec<Frame>,
+    trail: Vec<TrailEntry>,
+    // Per cell, the decision levels (1 = outermost) its domain was
+    // narrowed by; level 0 is the initial propagation. Backjumping only.
+    reasons: Vec<Bitset>,
+    // Levels behind the last propagation failure
+    conflict: Option<Bitset>,
+    // Learned combinations of (variable, value) that can't all hold
+    nogoods: Vec<Vec<(Var, usize)>>,
+    seed: Option<u64>,
+    rng: Option<ChaCha8Rng>,
+    // Backtrack count when the current restart began
+    restart_mark: u64,
+    found_fill: bool,
+    started: bool,
+    status: SearchStatus,
 }
 
 impl Solver {
-    pub fn new(grid: Grid, dawg: Dawg) -> Self {
+    pub fn new(grid: Grid, dawg: Rc<Dawg>) -> Self {
+        Self::with_options(grid, dawg, SolveOptions::default())
+    }
+
+    pub fn with_options(grid: Grid, dawg: Rc<Dawg>, options: SolveOptions) -> Self {
         let (slots, cell_to_slots) = Self::identify_slots(&grid);
+        let mut slots_by_length = vec![vec![]; grid.width.max(grid.height) + 1];
+        for slot in &slots {
+            slots_by_length[slot.length].push(slot.id);
+        }
+        let locked = vec![None; slots.len()];
+        let ignored = vec![false; slots.len()];
+        let weights = vec![1; slots.len()];
+        let stats = SolverStats {
+            variable_order: options.variable_order,
+            value_order: options.value_order,
+            ..SolverStats::default()
+        };
+        let deadline = options
+            .time_limit_ms
+            .map(|ms| Instant::now() + Duration::from_millis(ms));
+        let seed = match (options.seed, options.restarts) {
+            (Some(seed), _) => Some(seed),
+            (None, Restarts::None) => None,
+            (None, _) => Some(random_seed()),
+        };
         Solver {
             grid,
             dawg,
+            extra: None,
+            options,
             slots,
             cell_to_slots,
+            slots_by_length,
+            locked,
+            ignored,
+            stats,
+            deadline,
+            cancel: None,
+            best_partial: None,
+            index: None,
+            weights,
+            stack: Vec::new(),
+            trail: Vec::new(),
+            reasons: Vec::new(),
+            conflict: None,
+            nogoods: Vec::new(),
+            seed,
+            rng: seed.map(ChaCha8Rng::seed_from_u64),
+            restart_mark: 0,
+            found_fill: false,
+            started: false,
+            status: SearchStatus::Running,
+        }
+    }
+
+    pub fn set_cancel_check(&mut self, cancel: Rc<dyn Fn() -> bool>) {
+        self.cancel = Some(cancel);
+    }
+
+    // Seed of the random tie-breaking, if any; replaying with it gives the
+    // same search.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn stats(&self) -> &SolverStats {
+        &self.stats
+    }
+
+    // Hands over the counters so far, starting new ones for the same
+    // heuristics.
+    pub fn take_stats(&mut self) -> SolverStats {
+        let fresh = SolverStats {
+            variable_order: self.options.variable_order,
+            value_order: self.options.value_order,
+            ..SolverStats::default()
+        };
+        std::mem::replace(&mut self.stats, fresh)
+    }
+
+    // Carries on the counters of earlier runs for the same request, so the
+    // node and backtrack budgets cover all of them rather than each alone.
+    pub fn continue_stats(&mut self, stats: SolverStats) {
+        self.stats = stats;
+    }
+
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    pub fn set_extra_words(&mut self, extra: Option<Rc<Dawg>>) {
+        self.extra = extra;
+        self.index = None;
+    }
+
+    // Pins `word` into a slot. The slot is then exempt from the dictionary
+    // check, so words the dictionary doesn't know can be placed. Returns
+    // false if the word doesn't fit the slot's current letters.
+    pub fn lock_slot(&mut self, slot_idx: usize, word: &str, score: u8) -> bool {
+        let letters: Vec<char> = word.chars().map(|c| c.to_ascii_uppercase()).collect();
+        if letters.len() != self.slots[slot_idx].length {
+            return false;
+        }
+        for (i, &ch) in letters.iter().enumerate() {
+            let (r, c) = self.slots[slot_idx].cells[i];
+            let cell = self.grid.get_cell_mut(r, c);
+            let narrowed = cell.domain.intersect(Domain::from_char(ch));
+            if narrowed.is_empty() {
+                return false;
+            }
+            cell.domain = narrowed;
         }
+        let word = letters.iter().map(|&ch| ch as u8 - b'A').collect();
+        self.locked[slot_idx] = Some((word, score));
+        true
+    }
+
+    pub fn find_slot(&self, at: &SlotLocator) -> Option<usize> {
+        match *at {
+            SlotLocator::Id { slot } => (slot < self.slots.len()).then_some(slot),
+            SlotLocator::Cell { r, c, is_across } => self
+                .slots
+                .iter()
+                .find(|slot| slot.is_across == is_across && slot.cells.contains(&(r, c)))
+                .map(|slot| slot.id),
+        }
+    }
+
+    // Drops a slot's constraint, so its cells only have to fit the slots
+    // crossing them. For relaxing a grid before solving it.
+    pub fn ignore_slot(&mut self, slot_idx: usize) {
+        self.ignored[slot_idx] = true;
+        let length = self.slots[slot_idx].length;
+        self.slots_by_length[length].retain(|&other| other != slot_idx);
+    }
+
+    // Frees a fixed letter before solving.
+    pub fn unfix_cell(&mut self, r: usize, c: usize) {
+        let cell = self.grid.get_cell_mut(r, c);
+        if cell.fixed_char.take().is_some() {
+            cell.domain = Domain::full();
+        }
+    }
+
+    // Letters each position of a slot can take given the current domains,
+    // looking at that slot alone. None if no word fits it.
+    pub fn slot_letters(&self, slot_idx: usize) -> Option<Vec<Domain>> {
+        self.compute_slot_valid_masks(slot_idx)
     }
 
     fn identify_slots(grid: &Grid) -> (Vec<Slot>, Vec<Vec<usize>>) {
@@ -108,168 +538,1817 @@ impl Solver {
     // AC-3 Arc Consistency
     // Returns false if any domain becomes empty (inconsistency found)
     pub fn propagate(&mut self) -> bool {
-        let mut queue: VecDeque<usize> = (0..self.slots.len()).collect();
-        let mut in_queue = vec![true; self.slots.len()];
+        // Called before the search starts (e.g. to vet a theme placement),
+        // nothing has been decided yet, so there are no reasons to track
+        if self.options.backjumping && self.reasons.is_empty() {
+            self.reasons = vec![self.no_levels(); self.grid.cells.len()];
+        }
+        let queue: VecDeque<usize> = (0..self.slots.len()).filter(|&i| !self.ignored[i]).collect();
+        let in_queue = vec![true; self.slots.len()];
+        self.propagate_queue(queue, in_queue)
+    }
+
+    // Propagation after `cells` were narrowed in an otherwise consistent
+    // state: only the slots they touch can have lost support.
+    fn propagate_from(&mut self, cells: &[usize]) -> bool {
+        let mut queue = VecDeque::new();
+        let mut in_queue = vec![false; self.slots.len()];
+        for &cell_idx in cells {
+            self.enqueue_affected(cell_idx, None, &mut queue, &mut in_queue);
+        }
+        self.propagate_queue(queue, in_queue)
+    }
 
+    // Turns `cells` black in a propagated grid, cutting short the slots
+    // through them, and propagates again. On failure the grid is put back
+    // as it was. Much cheaper than propagating a fresh solver for the new
+    // layout, but not the same: the cut slots and the slots crossing them
+    // start over, while letters ruled out elsewhere stay ruled out, so it
+    // can turn down a square a fresh solver would allow.
+    pub fn place_black(&mut self, cells: &[(usize, usize)]) -> bool {
+        let trail_len = self.trail.len();
+        let slots_len = self.slots.len();
+        let mut cut = Vec::new();
+        let mut blackened = Vec::new();
+        for &(r, c) in cells {
+            let cell_idx = self.grid.get_index(r, c);
+            if self.grid.cells[cell_idx].is_black {
+                continue;
+            }
+            for &slot_idx in &self.cell_to_slots[cell_idx] {
+                if !self.ignored[slot_idx] && !cut.contains(&slot_idx) {
+                    cut.push(slot_idx);
+                }
+            }
+            self.set_domain(cell_idx, Domain::empty(), None);
+            self.grid.cells[cell_idx].is_black = true;
+            blackened.push(cell_idx);
+        }
+
+        // Each cut slot gives way to the runs of two or more white squares
+        // still in it
+        for &slot_idx in &cut {
+            self.ignore_slot(slot_idx);
+            let slot = self.slots[slot_idx].clone();
+            let white = |&(r, c): &(usize, usize)| !self.grid.get_cell(r, c).is_black;
+            let pieces: Vec<Vec<(usize, usize)>> = slot
+                .cells
+                .split(|cell| !white(cell))
+                .filter(|piece| piece.len() > 1)
+                .map(<[_]>::to_vec)
+                .collect();
+            for cells in pieces {
+                let id = self.slots.len();
+                for &(r, c) in &cells {
+                    let cell_idx = self.grid.get_index(r, c);
+                    self.cell_to_slots[cell_idx].push(id);
+                }
+                self.slots_by_length[cells.len()].push(id);
+                self.slots.push(Slot {
+                    id,
+                    r: cells[0].0,
+                    c: cells[0].1,
+                    length: cells.len(),
+                    is_across: slot.is_across,
+                    cells,
+                });
+                self.locked.push(None);
+                self.ignored.push(false);
+                self.weights.push(1);
+            }
+        }
+
+        // Letters the cut slots ruled out may be back, in their own cells
+        // and along every slot through those
+        let mut reset: Vec<usize> = Vec::new();
+        for &slot_idx in &cut {
+            for &(r, c) in &self.slots[slot_idx].cells {
+                let cell_idx = self.grid.get_index(r, c);
+                for &crossing in &self.cell_to_slots[cell_idx] {
+                    if self.ignored[crossing] {
+                        continue;
+                    }
+                    for &(r, c) in &self.slots[crossing].cells {
+                        let cell_idx = self.grid.get_index(r, c);
+                        if !reset.contains(&cell_idx) {
+                            reset.push(cell_idx);
+                        }
+                    }
+                }
+            }
+        }
+        for &cell_idx in &reset {
+            let cell = &self.grid.cells[cell_idx];
+            let domain = cell.fixed_char.map_or(Domain::full(), Domain::from_char);
+            if cell.domain != domain {
+                self.set_domain(cell_idx, domain, None);
+            }
+        }
+
+        if self.propagate_from(&reset) {
+            return true;
+        }
+        self.undo_to(trail_len);
+        for &cell_idx in &blackened {
+            self.grid.cells[cell_idx].is_black = false;
+        }
+        for slot in self.slots.drain(slots_len..) {
+            for &(r, c) in &slot.cells {
+                let cell_idx = self.grid.get_index(r, c);
+                self.cell_to_slots[cell_idx].retain(|&other| other < slots_len);
+            }
+            self.slots_by_length[slot.length].retain(|&other| other < slots_len);
+        }
+        self.locked.truncate(slots_len);
+        self.ignored.truncate(slots_len);
+        self.weights.truncate(slots_len);
+        for &slot_idx in &cut {
+            self.ignored[slot_idx] = false;
+            self.slots_by_length[self.slots[slot_idx].length].push(slot_idx);
+        }
+        false
+    }
+
+    fn propagate_queue(&mut self, mut queue: VecDeque<usize>, mut in_queue: Vec<bool>) -> bool {
         while let Some(slot_idx) = queue.pop_front() {
             in_queue[slot_idx] = false;
+            self.stats.propagations += 1;
             
             // Calculate valid letters for each cell in this slot based on the DAWG
             // and the CURRENT domains of the cells.
             if let Some(valid_masks) = self.compute_slot_valid_masks(slot_idx) {
-                let slot = &self.slots[slot_idx];
-                
-                for (i, &(r, c)) in slot.cells.iter().enumerate() {
+                for (i, &valid) in valid_masks.iter().enumerate() {
+                    let (r, c) = self.slots[slot_idx].cells[i];
                     let cell_idx = self.grid.get_index(r, c);
                     let current_domain = self.grid.cells[cell_idx].domain;
-                    let new_domain = current_domain.intersect(valid_masks[i]);
+                    let new_domain = current_domain.intersect(valid);
 
                     if new_domain != current_domain {
+                        let because = self.explain_slot(slot_idx);
                         if new_domain.is_empty() {
+                            self.conflict = because;
+                            self.weights[slot_idx] += 1;
                             return false; // Domain wiped out, invalid state
                         }
                         
                         // Update cell domain
-                        self.grid.cells[cell_idx].domain = new_domain;
-
-                        // Add all intersecting slots to queue
-                        for &neighbor_slot_idx in &self.cell_to_slots[cell_idx] {
-                            if neighbor_slot_idx != slot_idx && !in_queue[neighbor_slot_idx] {
-                                queue.push_back(neighbor_slot_idx);
-                                in_queue[neighbor_slot_idx] = true;
-                            }
-                        }
+                        self.set_domain(cell_idx, new_domain, because.as_ref());
+                        self.enqueue_affected(cell_idx, Some(slot_idx), &mut queue, &mut in_queue);
                     }
                 }
             } else {
+                self.conflict = self.explain_slot(slot_idx);
+                self.weights[slot_idx] += 1;
                 return false; // No words fit this slot
             }
         }
         true
     }
 
+    // Queues the slots to revise after `cell_idx` changed, other than the
+    // slot that changed it.
+    fn enqueue_affected(
+        &self,
+        cell_idx: usize,
+        from_slot: Option<usize>,
+        queue: &mut VecDeque<usize>,
+        in_queue: &mut [bool],
+    ) {
+        // Add all intersecting slots to queue
+        for &neighbor_slot_idx in &self.cell_to_slots[cell_idx] {
+            if Some(neighbor_slot_idx) != from_slot && !in_queue[neighbor_slot_idx] && !self.ignored[neighbor_slot_idx] {
+                queue.push_back(neighbor_slot_idx);
+                in_queue[neighbor_slot_idx] = true;
+            }
+        }
+
+        // A slot that just became a whole word rules that word
+        // out for every other slot of the same length.
+        if !self.options.allow_duplicates {
+            for &touched in &self.cell_to_slots[cell_idx] {
+                if self.ignored[touched] || self.slot_word(touched).is_none() {
+                    continue;
+                }
+                for &other in &self.slots_by_length[self.slots[touched].length] {
+                    if other != touched && !in_queue[other] {
+                        queue.push_back(other);
+                        in_queue[other] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    // Search-time domain changes go through here so backtracking can undo
+    // them. `because` is the levels responsible when backjumping.
+    fn set_domain(&mut self, cell_idx: usize, domain: Domain, because: Option<&Bitset>) {
+        let reason = because.map(|because| {
+            let mut reason = self.reasons[cell_idx].clone();
+            reason.union_with(because);
+            std::mem::replace(&mut self.reasons[cell_idx], reason)
+        });
+        let cell = &mut self.grid.cells[cell_idx];
+        self.trail.push(TrailEntry {
+            cell_idx,
+            domain: cell.domain,
+            reason,
+        });
+        cell.domain = domain;
+    }
+
+    fn undo_to(&mut self, trail_len: usize) {
+        while self.trail.len() > trail_len {
+            let entry = self.trail.pop().unwrap();
+            self.grid.cells[entry.cell_idx].domain = entry.domain;
+            if let Some(reason) = entry.reason {
+                self.reasons[entry.cell_idx] = reason;
+            }
+        }
+    }
+
+    // The levels that narrowed a slot's cells, and the cells of the
+    // same-length slots whose words it may not repeat. None unless
+    // backjumping.
+    fn explain_slot(&self, slot_idx: usize) -> Option<Bitset> {
+        if !self.options.backjumping {
+            return None;
+        }
+        let mut levels = self.no_levels();
+        let slot = &self.slots[slot_idx];
+        for &(r, c) in &slot.cells {
+            levels.union_with(&self.reasons[self.grid.get_index(r, c)]);
+        }
+        if !self.options.allow_duplicates {
+            for &other in &self.slots_by_length[slot.length] {
+                if other != slot_idx && self.slot_word(other).is_some() {
+                    for &(r, c) in &self.slots[other].cells {
+                        levels.union_with(&self.reasons[self.grid.get_index(r, c)]);
+                    }
+                }
+            }
+        }
+        Some(levels)
+    }
+
+    fn no_levels(&self) -> Bitset {
+        // Decisions never outnumber cells
+        Bitset::empty(self.grid.cells.len() + 1)
+    }
+
+    // False, with the conflict set, if the grid now satisfies every
+    // literal of some learned nogood.
+    fn check_nogoods(&mut self) -> bool {
+        let violated = self.nogoods.iter().position(|nogood| {
+            nogood.iter().all(|&(var, value)| match var {
+                Var::Cell(cell_idx) => {
+                    self.grid.cells[cell_idx].domain.is_singleton() == Some((b'A' + value as u8) as char)
+                }
+                Var::Slot(slot_idx) => {
+                    let index = self.index.as_ref().unwrap();
+                    let by_length = index.length(self.slots[slot_idx].length).unwrap();
+                    self.slot_word(slot_idx).is_some_and(|word| word == by_length.word(value))
+                }
+            })
+        });
+        let Some(i) = violated else {
+            return true;
+        };
+        let mut levels = self.no_levels();
+        for &(var, _) in &self.nogoods[i] {
+            match var {
+                Var::Cell(cell_idx) => levels.union_with(&self.reasons[cell_idx]),
+                Var::Slot(slot_idx) => {
+                    for &(r, c) in &self.slots[slot_idx].cells {
+                        levels.union_with(&self.reasons[self.grid.get_index(r, c)]);
+                    }
+                }
+            }
+        }
+        self.conflict = Some(levels);
+        false
+    }
+
     // Computes the bitmask of valid letters for each position in the slot
     // by traversing the DAWG with the current cell constraints.
     fn compute_slot_valid_masks(&self, slot_idx: usize) -> Option<Vec<Domain>> {
+        match &self.index {
+            Some(index) if self.locked[slot_idx].is_none() => {
+                let slot = &self.slots[slot_idx];
+                let by_length = index.length(slot.length)?;
+                let words = self.slot_candidates(index, slot_idx)?;
+                Some((0..slot.length).map(|pos| by_length.letters_at(&words, pos)).collect())
+            }
+            _ => self.scan_slot(slot_idx).map(|(masks, _)| masks),
+        }
+    }
+
+    // Indexed words that still fit a slot, or None if none do.
+    fn slot_candidates(&self, index: &WordIndex, slot_idx: usize) -> Option<Bitset> {
+        let slot = &self.slots[slot_idx];
+        let by_length = index.length(slot.length)?;
+        let domains: Vec<Domain> = slot
+            .cells
+            .iter()
+            .map(|&(r, c)| self.grid.get_cell(r, c).domain)
+            .collect();
+        let mut words = by_length.matching(&domains);
+        if !self.options.allow_duplicates {
+            for &other in &self.slots_by_length[slot.length] {
+                if other == slot_idx {
+                    continue;
+                }
+                if let Some(id) = self.slot_word(other).and_then(|word| by_length.find(&word)) {
+                    words.remove(id);
+                }
+            }
+        }
+        if words.is_empty() { None } else { Some(words) }
+    }
+
+    // Same traversal as `compute_slot_valid_masks`, also returning for each
+    // position and letter the best score of any word that fits the slot with
+    // that letter there.
+    fn scan_slot(&self, slot_idx: usize) -> Option<(Vec<Domain>, Vec<[u8; 26]>)> {
         let slot = &self.slots[slot_idx];
+        // Words already fixed in other slots of this length (all-different)
+        let mut excluded = Vec::new();
+        if !self.options.allow_duplicates {
+            for &other in &self.slots_by_length[slot.length] {
+                if other != slot_idx {
+                    excluded.extend(self.slot_word(other));
+                }
+            }
+        }
+        let domains: Vec<Domain> = slot
+            .cells
+            .iter()
+            .map(|&(r, c)| self.grid.get_cell(r, c).domain)
+            .collect();
         let mut masks = vec![Domain::empty(); slot.length];
-        
-        // Recursive DFS on DAWG to find all matching words
-        let found = self.find_valid_paths(
-            0, 
-            0, // Root of DAWG
-            slot, 
-            &mut masks
-        );
+        let mut best = vec![[0u8; 26]; slot.length];
 
-        if !found {
-            return None;
+        if let Some((word, score)) = &self.locked[slot_idx] {
+            if excluded.contains(word) {
+                return None;
+            }
+            for (pos, &label) in word.iter().enumerate() {
+                let ch = (b'A' + label) as char;
+                if !domains[pos].contains(ch) {
+                    return None;
+                }
+                masks[pos] = Domain::from_char(ch);
+                best[pos][label as usize] = *score;
+            }
+            return Some((masks, best));
+        }
+
+        let mut visit = |word: &[u8], score: u8| {
+            if score >= self.options.min_score && !excluded.iter().any(|w| w == word) {
+                for (pos, &label) in word.iter().enumerate() {
+                    masks[pos] = masks[pos].union(Domain::from_char((b'A' + label) as char));
+                    let letter_best = &mut best[pos][label as usize];
+                    *letter_best = (*letter_best).max(score);
+                }
+            }
+            true
+        };
+        self.dawg.walk_matching(&domains, slot.length..=slot.length, &mut visit);
+        if let Some(extra) = &self.extra {
+            extra.walk_matching(&domains, slot.length..=slot.length, &mut visit);
         }
 
         // If any position has an empty mask, then no valid word exists
         if masks.iter().any(|m| m.is_empty()) {
             None
         } else {
-            Some(masks)
+            Some((masks, best))
+        }
+    }
+
+    // The slot's word as letter indices, if every cell is down to one letter.
+    fn slot_word(&self, slot_idx: usize) -> Option<Vec<u8>> {
+        self.slots[slot_idx]
+            .cells
+            .iter()
+            .map(|&(r, c)| {
+                self.grid
+                    .get_cell(r, c)
+                    .domain
+                    .is_singleton()
+                    .map(|ch| ch as u8 - b'A')
+            })
+            .collect()
+    }
+
+    // Candidate letters for a cell in the order the search should try them.
+    fn ordered_values(&mut self, cell_idx: usize) -> Vec<char> {
+        let mut values: Vec<char> = self.grid.cells[cell_idx].domain.iter().collect();
+        if self.options.value_order == ValueOrder::Alphabetical {
+            return values;
+        }
+        if let Some(rng) = &mut self.rng {
+            values.shuffle(rng);
+        }
+        // Stable sorts, so ties stay alphabetical (or shuffled if seeded)
+        if self.options.value_order == ValueOrder::Score {
+            let mut totals = [0u32; 26];
+            for &slot_idx in &self.cell_to_slots[cell_idx] {
+                if self.ignored[slot_idx] {
+                    continue;
+                }
+                let pos = self.position_in(slot_idx, cell_idx);
+                if let Some((_, best)) = self.scan_slot(slot_idx) {
+                    for (total, &score) in totals.iter_mut().zip(best[pos].iter()) {
+                        *total += score as u32;
+                    }
+                }
+            }
+            values.sort_by_key(|&ch| Reverse(totals[(ch as u8 - b'A') as usize]));
+        } else {
+            let support: Vec<f64> = (0..26).map(|label| self.crossing_support(cell_idx, None, label)).collect();
+            values.sort_by(|a, b| support[(*b as u8 - b'A') as usize].total_cmp(&support[(*a as u8 - b'A') as usize]));
+        }
+        values
+    }
+
+    fn position_in(&self, slot_idx: usize, cell_idx: usize) -> usize {
+        self.slots[slot_idx]
+            .cells
+            .iter()
+            .position(|&(r, c)| self.grid.get_index(r, c) == cell_idx)
+            .unwrap()
+    }
+
+    // How common `label` is in the dictionary at this cell's position in
+    // each of its slots other than `skip`, summed.
+    fn crossing_support(&self, cell_idx: usize, skip: Option<usize>, label: u8) -> f64 {
+        let Some(index) = self.index.as_ref() else {
+            return 0.0;
+        };
+        self.cell_to_slots[cell_idx]
+            .iter()
+            .filter(|&&slot_idx| Some(slot_idx) != skip && !self.ignored[slot_idx])
+            .filter_map(|&slot_idx| {
+                let by_length = index.length(self.slots[slot_idx].length)?;
+                Some(by_length.frequency(self.position_in(slot_idx, cell_idx), label))
+            })
+            .sum()
+    }
+
+    // Runs the search to the end.
+    pub fn solve(&mut self) -> SolveOutcome {
+        loop {
+            match self.step(usize::MAX) {
+                SearchStatus::Running => continue,
+                SearchStatus::Solved => return SolveOutcome::Solved(self.grid.clone()),
+                SearchStatus::Unsatisfiable => return SolveOutcome::Unsatisfiable,
+                SearchStatus::GaveUp(reason) => {
+                    return SolveOutcome::GaveUp {
+                        reason,
+                        best_partial: self.best_partial().clone(),
+                    }
+                }
+            }
         }
     }
 
-    fn find_valid_paths(
-        &self, 
-        pos: usize, 
-        node_idx: usize, 
-        slot: &Slot, 
-        masks: &mut Vec<Domain>
-    ) -> bool {
-        // Base case: end of slot
-        if pos == slot.length {
-            return self.dawg.nodes[node_idx].is_terminal;
+    // Resumes after the last fill returned and runs to the next one. Fills
+    // come out distinct; Unsatisfiable means there are no more.
+    pub fn next_solution(&mut self) -> SolveOutcome {
+        if self.status == SearchStatus::Solved {
+            self.status = SearchStatus::Running;
         }
+        self.solve()
+    }
 
-        let (r, c) = slot.cells[pos];
-        let cell_domain = self.grid.get_cell(r, c).domain;
-        let node = &self.dawg.nodes[node_idx];
-        
-        let mut found_path = false;
+    // Successive distinct fills, until there are no more or a budget runs
+    // out (check `status` to tell which).
+    pub fn solutions(&mut self) -> impl Iterator<Item = Grid> + '_ {
+        std::iter::from_fn(move || match self.next_solution() {
+            SolveOutcome::Solved(grid) => Some(grid),
+            _ => None,
+        })
+    }
 
-        // Try all transitions that are valid in the current cell's domain
-        for char_code in 0..26 {
-            let char_char = (b'A' + char_code as u8) as char;
-            
-            if cell_domain.contains(char_char) {
-                if let Some(&next_node_idx) = node.children.get(&char_char) {
-                    // Recurse
-                    if self.find_valid_paths(pos + 1, next_node_idx, slot, masks) {
-                        found_path = true;
-                        // Add this char to the valid mask for this position
-                        masks[pos] = masks[pos].union(Domain::from_char(char_char));
+    // Runs through the fills, counting up to `limit` of them and keeping
+    // the `keep` best by total slot score.
+    pub fn enumerate(&mut self, limit: Option<usize>, keep: usize) -> Enumeration {
+        let mut found = Enumeration {
+            count: 0,
+            best: Vec::new(),
+            complete: false,
+            stopped: None,
+        };
+        let mut ranked: Vec<(u32, Grid)> = Vec::new();
+        loop {
+            if limit.is_some_and(|limit| found.count >= limit) {
+                break;
+            }
+            match self.next_solution() {
+                SolveOutcome::Solved(grid) => {
+                    found.count += 1;
+                    if keep == 0 {
+                        continue;
+                    }
+                    let total = self.slot_entries(&grid).iter().map(|e| e.score as u32).sum();
+                    // After any equal-scoring fills, so ties keep search order
+                    let at = ranked.partition_point(|(t, _)| *t >= total);
+                    if at < keep {
+                        ranked.insert(at, (total, grid));
+                        ranked.truncate(keep);
                     }
                 }
+                SolveOutcome::Unsatisfiable => {
+                    found.complete = true;
+                    break;
+                }
+                SolveOutcome::GaveUp { reason, .. } => {
+                    found.stopped = Some(reason);
+                    break;
+                }
             }
         }
-        
-        found_path
+        found.best = ranked.into_iter().map(|(_, grid)| grid).collect();
+        found
+    }
+
+    // Counts fills up to `limit`. A complete count of 1 means the grid
+    // only has one fill.
+    pub fn count_solutions(&mut self, limit: usize) -> Enumeration {
+        self.enumerate(Some(limit), 0)
     }
 
-    pub fn solve(&mut self) -> Option<Grid> {
-        // Initial propagation
+    // The `k` best fills by total slot score, among those the budgets let
+    // the search reach.
+    pub fn best_solutions(&mut self, k: usize) -> Enumeration {
+        self.enumerate(None, k)
+    }
+
+    // Advances the search by up to `steps` values tried, stopping early once
+    // it finishes. A finished search keeps reporting how it finished.
+    pub fn step(&mut self, steps: usize) -> SearchStatus {
+        let started = Instant::now();
+        for _ in 0..steps {
+            if self.status != SearchStatus::Running {
+                break;
+            }
+            self.status = self.advance();
+        }
+        self.stats.elapsed_ms += started.elapsed().as_secs_f64() * 1000.0;
+        self.status
+    }
+
+    // How the search stands without advancing it
+    pub fn status(&self) -> SearchStatus {
+        self.status
+    }
+
+    // Current domains of every cell. Once solved, this is the solution.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    // Number of open decisions
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // The most-filled consistent state seen so far
+    pub fn best_partial(&self) -> &Grid {
+        match &self.best_partial {
+            Some((_, grid)) => grid,
+            None => &self.grid,
+        }
+    }
+
+    // Which budget, if any, has run out.
+    pub fn check_limits(&self) -> Result<(), Limit> {
+        if self.options.max_nodes.is_some_and(|max| self.stats.nodes >= max) {
+            return Err(Limit::Nodes);
+        }
+        if self.options.max_backtracks.is_some_and(|max| self.stats.backtracks >= max) {
+            return Err(Limit::Backtracks);
+        }
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(Limit::Time);
+        }
+        if self.cancel.as_ref().is_some_and(|cancel| cancel()) {
+            return Err(Limit::Cancelled);
+        }
+        Ok(())
+    }
+
+    // Remembers the current state if it has more decided cells than any
+    // state seen so far, to hand back when a budget runs out.
+    fn record_partial(&mut self) {
+        let decided = self
+            .grid
+            .cells
+            .iter()
+            .filter(|c| !c.is_black && c.domain.count() == 1)
+            .count();
+        if self.best_partial.as_ref().is_none_or(|(best, _)| decided > *best) {
+            self.best_partial = Some((decided, self.grid.clone()));
+        }
+    }
+
+    // One step: the initial propagation, or trying the next value of the
+    // innermost decision that has one left.
+    fn advance(&mut self) -> SearchStatus {
+        if !self.started {
+            self.started = true;
+            if self.options.backjumping {
+                self.reasons = vec![self.no_levels(); self.grid.cells.len()];
+            }
+            let needs_index = self.options.model == SearchModel::Word
+                || self.options.variable_order == VariableOrder::SlotMrv
+                || self.options.value_order == ValueOrder::Lcv;
+            if needs_index {
+                self.build_index();
+            }
+            if !self.propagate() {
+                return SearchStatus::Unsatisfiable;
+            }
+            return self.descend();
+        }
+
+        // Undo decisions that have run out of values
+        loop {
+            match self.stack.last() {
+                None => return SearchStatus::Unsatisfiable,
+                Some(frame) if frame.next < frame.values.len() => break,
+                Some(_) => {
+                    let frame = self.stack.pop().unwrap();
+                    self.undo_to(frame.trail_len);
+                    if self.options.backjumping {
+                        self.backjump(frame.conflict);
+                    }
+                    // ...which means the value that led to it failed
+                    if !self.stack.is_empty() {
+                        self.stats.backtracks += 1;
+                    }
+                }
+            }
+        }
+        if let Err(reason) = self.check_limits() {
+            return SearchStatus::GaveUp(reason);
+        }
+        if self.restart_due() {
+            return self.restart();
+        }
+
+        let frame = self.stack.last_mut().unwrap();
+        let (var, value, trail_len) = (frame.var, frame.values[frame.next], frame.trail_len);
+        frame.next += 1;
+        self.undo_to(trail_len);
+        self.stats.nodes += 1;
+
+        if self.assign(var, value) {
+            self.descend()
+        } else {
+            if let Some(mut conflict) = self.conflict.take() {
+                let level = self.stack.len();
+                conflict.remove(level);
+                self.stack[level - 1].conflict.union_with(&conflict);
+            }
+            self.stats.backtracks += 1;
+            SearchStatus::Running
+        }
+    }
+
+    fn build_index(&mut self) {
+        if self.index.is_none() {
+            let mut dawgs = vec![&*self.dawg];
+            dawgs.extend(self.extra.as_deref());
+            self.index = Some(Rc::new(WordIndex::new(&dawgs, self.options.min_score)));
+        }
+    }
+
+    // The words that fit a slot and leave the rest of the grid consistent
+    // after propagation, with the candidates each leaves its crossings.
+    // Ranked by `value_order`: best score first, fewest crossings cut off
+    // first (lcv), or alphabetical. None if the grid is already
+    // inconsistent. Call on a solver that hasn't started searching.
+    pub fn slot_options(&mut self, slot_idx: usize) -> Option<SlotOptions> {
+        let started = Instant::now();
+        if self.options.backjumping {
+            self.reasons = vec![self.no_levels(); self.grid.cells.len()];
+        }
+        self.build_index();
         if !self.propagate() {
+            self.stats.elapsed_ms += started.elapsed().as_secs_f64() * 1000.0;
             return None;
         }
+        let index = self.index.clone().unwrap();
+        let by_length = index.length(self.slots[slot_idx].length)?;
+        let words = self.slot_candidates(&index, slot_idx)?;
+        let mut crossing: Vec<usize> = Vec::new();
+        for &(r, c) in &self.slots[slot_idx].cells {
+            for &other in &self.cell_to_slots[self.grid.get_index(r, c)] {
+                if other != slot_idx && !self.ignored[other] {
+                    crossing.push(other);
+                }
+            }
+        }
+
+        let mut found = SlotOptions {
+            words: Vec::new(),
+            complete: false,
+            stopped: None,
+        };
+        for id in words.iter() {
+            if let Err(reason) = self.check_limits() {
+                found.stopped = Some(reason);
+                break;
+            }
+            self.stats.nodes += 1;
+            let trail_len = self.trail.len();
+            if self.assign(Var::Slot(slot_idx), id) {
+                let counts: Vec<u32> = crossing
+                    .iter()
+                    .map(|&other| self.slot_candidates(&index, other).map_or(0, |words| words.count()))
+                    .collect();
+                found.words.push(SlotOption {
+                    word: by_length.word(id).iter().map(|&label| (b'A' + label) as char).collect(),
+                    score: by_length.score(id),
+                    options: counts.iter().map(|&n| n as u64).sum(),
+                    tightest: counts.iter().copied().min().unwrap_or(0),
+                });
+            } else {
+                self.stats.backtracks += 1;
+            }
+            self.conflict = None;
+            self.undo_to(trail_len);
+        }
+        found.complete = found.stopped.is_none();
+        self.stats.elapsed_ms += started.elapsed().as_secs_f64() * 1000.0;
+
+        // Stable sorts over alphabetical ids
+        match self.options.value_order {
+            ValueOrder::Alphabetical => {}
+            ValueOrder::Score => found.words.sort_by_key(|w| (Reverse(w.score), Reverse(w.tightest), Reverse(w.options))),
+            ValueOrder::Lcv => found.words.sort_by_key(|w| (Reverse(w.tightest), Reverse(w.options), Reverse(w.score))),
+        }
+        Some(found)
+    }
+
+    fn restart_due(&self) -> bool {
+        if self.found_fill {
+            return false;
+        }
+        let base = self.options.restart_base.max(1);
+        let n = self.stats.restarts;
+        let cutoff = match self.options.restarts {
+            Restarts::None => return false,
+            Restarts::Luby => base.saturating_mul(luby(n + 1)),
+            Restarts::Geometric => (base as f64 * 1.5f64.powi(n.min(100) as i32)) as u64,
+        };
+        self.stats.backtracks - self.restart_mark >= cutoff
+    }
+
+    // Drops every open decision and starts again from the initial
+    // propagation. Learned nogoods are kept.
+    fn restart(&mut self) -> SearchStatus {
+        while let Some(frame) = self.stack.pop() {
+            self.undo_to(frame.trail_len);
+        }
+        self.stats.restarts += 1;
+        self.restart_mark = self.stats.backtracks;
+        self.descend()
+    }
+
+    // After a decision ran out of values: unwinds to the latest level in
+    // its conflict set, whose current value is the one to blame, skipping
+    // any decisions in between. An empty conflict set means no earlier
+    // choice could have helped.
+    fn backjump(&mut self, conflict: Bitset) {
+        let Some(target) = conflict.last() else {
+            while let Some(frame) = self.stack.pop() {
+                self.undo_to(frame.trail_len);
+                self.stats.backjumps += 1;
+            }
+            return;
+        };
+        if self.options.learn_nogoods && conflict.count() as usize <= MAX_NOGOOD_LEN && self.nogoods.len() < MAX_NOGOODS {
+            let nogood = conflict
+                .iter()
+                .filter(|&level| level > 0)
+                .map(|level| {
+                    let frame = &self.stack[level - 1];
+                    (frame.var, frame.values[frame.next - 1])
+                })
+                .collect();
+            self.nogoods.push(nogood);
+            self.stats.nogoods += 1;
+        }
+        while self.stack.len() > target {
+            let frame = self.stack.pop().unwrap();
+            self.undo_to(frame.trail_len);
+            self.stats.backjumps += 1;
+        }
+        let frame = self.stack.last_mut().unwrap();
+        frame.conflict.union_with(&conflict);
+        frame.conflict.remove(target);
+    }
+
+    // Narrows the grid to one value of a variable and propagates. False if
+    // that leaves the grid inconsistent.
+    fn assign(&mut self, var: Var, value: usize) -> bool {
+        let because = self.options.backjumping.then(|| {
+            let mut level = self.no_levels();
+            level.insert(self.stack.len());
+            level
+        });
+        let consistent = match var {
+            Var::Cell(cell_idx) => {
+                self.set_domain(cell_idx, Domain::from_char((b'A' + value as u8) as char), because.as_ref());
+                self.propagate_from(&[cell_idx])
+            }
+            Var::Slot(slot_idx) => {
+                let index = self.index.clone().unwrap();
+                let word = index.length(self.slots[slot_idx].length).unwrap().word(value);
+                let mut changed = Vec::new();
+                for (pos, &label) in word.iter().enumerate() {
+                    let (r, c) = self.slots[slot_idx].cells[pos];
+                    let cell_idx = self.grid.get_index(r, c);
+                    let letter = Domain::from_char((b'A' + label) as char);
+                    if self.grid.cells[cell_idx].domain != letter {
+                        self.set_domain(cell_idx, letter, because.as_ref());
+                        changed.push(cell_idx);
+                    }
+                }
+                self.propagate_from(&changed)
+            }
+        };
+        consistent && (self.nogoods.is_empty() || self.check_nogoods())
+    }
+
+    // From a consistent state: done if every cell is decided, otherwise
+    // opens a decision on the next variable.
+    fn descend(&mut self) -> SearchStatus {
+        let Some(var) = self.select_var() else {
+            self.found_fill = true;
+            // Later values of every open decision may lead to other fills,
+            // so enumeration past this one has to backtrack chronologically.
+            if self.options.backjumping {
+                for (i, frame) in self.stack.iter_mut().enumerate() {
+                    for level in 1..=i {
+                        frame.conflict.insert(level);
+                    }
+                }
+            }
+            return SearchStatus::Solved;
+        };
+        self.record_partial();
+        // Whatever pruned this variable's domain is to blame for the values
+        // it won't get to try.
+        let conflict = match var {
+            Var::Cell(cell_idx) if self.options.backjumping => self.reasons[cell_idx].clone(),
+            Var::Slot(slot_idx) => self.explain_slot(slot_idx).unwrap_or_else(|| self.no_levels()),
+            Var::Cell(_) => self.no_levels(),
+        };
+        let values = match var {
+            Var::Cell(cell_idx) => self
+                .ordered_values(cell_idx)
+                .into_iter()
+                .map(|ch| (ch as u8 - b'A') as usize)
+                .collect(),
+            Var::Slot(slot_idx) => self.ordered_words(slot_idx),
+        };
+        self.stack.push(Frame {
+            var,
+            values,
+            next: 0,
+            trail_len: self.trail.len(),
+            conflict,
+        });
+        self.stats.max_depth = self.stats.max_depth.max(self.stack.len() as u64);
+        SearchStatus::Running
+    }
+
+    // The unfilled slots with the fewest candidate words
+    fn select_slots(&self) -> Vec<usize> {
+        let Some(index) = self.index.as_ref() else {
+            return Vec::new();
+        };
+        let mut min_count = u32::MAX;
+        let mut tied = Vec::new();
+        for slot_idx in 0..self.slots.len() {
+            if self.ignored[slot_idx] || self.slot_word(slot_idx).is_some() {
+                continue;
+            }
+            let count = self.slot_candidates(index, slot_idx).map_or(0, |words| words.count());
+            if count < min_count {
+                min_count = count;
+                tied.clear();
+            }
+            if count == min_count {
+                tied.push(slot_idx);
+            }
+        }
+        tied
+    }
+
+    // The first of equally good candidates, or a random one if seeded.
+    fn break_tie(&mut self, tied: &[usize]) -> Option<usize> {
+        match &mut self.rng {
+            Some(rng) => tied.choose(rng).copied(),
+            None => tied.first().copied(),
+        }
+    }
+
+    // A slot's candidate words in the order the search should try them.
+    fn ordered_words(&mut self, slot_idx: usize) -> Vec<usize> {
+        let index = self.index.clone().unwrap();
+        let Some(words) = self.slot_candidates(&index, slot_idx) else {
+            return Vec::new();
+        };
+        let mut ids: Vec<usize> = words.iter().collect();
+        if self.options.value_order == ValueOrder::Alphabetical {
+            return ids;
+        }
+        if let Some(rng) = &mut self.rng {
+            ids.shuffle(rng);
+        }
+        let by_length = index.length(self.slots[slot_idx].length).unwrap();
+        // Stable sorts, so ties stay alphabetical (or shuffled if seeded)
+        if self.options.value_order == ValueOrder::Score {
+            ids.sort_by_key(|&id| Reverse(by_length.score(id)));
+        } else {
+            // Only undecided cells constrain their crossings any further
+            let open: Vec<(usize, usize)> = self.slots[slot_idx]
+                .cells
+                .iter()
+                .enumerate()
+                .map(|(pos, &(r, c))| (pos, self.grid.get_index(r, c)))
+                .filter(|&(_, cell_idx)| self.grid.cells[cell_idx].domain.count() > 1)
+                .collect();
+            let support = |id: usize| -> f64 {
+                let word = by_length.word(id);
+                open.iter()
+                    .map(|&(pos, cell_idx)| self.crossing_support(cell_idx, Some(slot_idx), word[pos]))
+                    .sum()
+            };
+            let mut keyed: Vec<(f64, usize)> = ids.iter().map(|&id| (support(id), id)).collect();
+            keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+            ids = keyed.into_iter().map(|(_, id)| id).collect();
+        }
+        ids
+    }
 
-        self.backtrack()
+    // Picks the next variable under the configured heuristic. None once
+    // every cell is decided.
+    fn select_var(&mut self) -> Option<Var> {
+        if self.options.model == SearchModel::Word {
+            let tied = match self.options.variable_order {
+                VariableOrder::DomWdeg => self.select_slots_wdeg(),
+                VariableOrder::Mrv | VariableOrder::SlotMrv => self.select_slots(),
+            };
+            if let Some(slot_idx) = self.break_tie(&tied) {
+                return Some(Var::Slot(slot_idx));
+            }
+            // Only cells outside every slot are left
+        }
+        let tied = match self.options.variable_order {
+            VariableOrder::Mrv => self.select_cells(),
+            VariableOrder::DomWdeg => self.select_cells_wdeg(),
+            VariableOrder::SlotMrv => {
+                let slots = self.select_slots();
+                match self.break_tie(&slots) {
+                    Some(slot_idx) => self.select_cells_in(slot_idx),
+                    None => self.select_cells(),
+                }
+            }
+        };
+        self.break_tie(&tied).map(Var::Cell)
     }
 
-    fn backtrack(&mut self) -> Option<Grid> {
-        // Check if solved
-        if self.is_solved() {
-            return Some(self.grid.clone());
+    // Dom/wdeg over slots: candidate words per unit of weight on the slot
+    // and the slots crossing it.
+    fn select_slots_wdeg(&self) -> Vec<usize> {
+        let Some(index) = self.index.as_ref() else {
+            return Vec::new();
+        };
+        let mut best = f64::INFINITY;
+        let mut tied = Vec::new();
+        for slot_idx in 0..self.slots.len() {
+            if self.ignored[slot_idx] || self.slot_word(slot_idx).is_some() {
+                continue;
+            }
+            let count = self.slot_candidates(index, slot_idx).map_or(0, |words| words.count());
+            let mut wdeg = self.weights[slot_idx];
+            for &(r, c) in &self.slots[slot_idx].cells {
+                for &other in &self.cell_to_slots[self.grid.get_index(r, c)] {
+                    if other != slot_idx {
+                        wdeg += self.weights[other];
+                    }
+                }
+            }
+            let ratio = count as f64 / wdeg as f64;
+            if ratio < best {
+                best = ratio;
+                tied.clear();
+            }
+            if ratio == best {
+                tied.push(slot_idx);
+            }
         }
+        tied
+    }
 
-        // MRV Heuristic: Find cell with minimum remaining values (> 1)
+    // Dom/wdeg over cells: remaining letters per unit of weight on the
+    // cell's slots.
+    fn select_cells_wdeg(&self) -> Vec<usize> {
+        let mut best = f64::INFINITY;
+        let mut tied = Vec::new();
+        for (i, cell) in self.grid.cells.iter().enumerate() {
+            let count = cell.domain.count();
+            if cell.is_black || count <= 1 {
+                continue;
+            }
+            let wdeg: u32 = self.cell_to_slots[i].iter().map(|&slot_idx| self.weights[slot_idx]).sum();
+            let ratio = count as f64 / wdeg.max(1) as f64;
+            if ratio < best {
+                best = ratio;
+                tied.clear();
+            }
+            if ratio == best {
+                tied.push(i);
+            }
+        }
+        tied
+    }
+
+    // The undecided cells of a slot with the fewest remaining letters
+    fn select_cells_in(&self, slot_idx: usize) -> Vec<usize> {
+        let mut min_count = 32;
+        let mut tied = Vec::new();
+        for &(r, c) in &self.slots[slot_idx].cells {
+            let cell_idx = self.grid.get_index(r, c);
+            let count = self.grid.cells[cell_idx].domain.count();
+            if count <= 1 || count > min_count {
+                continue;
+            }
+            if count < min_count {
+                min_count = count;
+                tied.clear();
+            }
+            tied.push(cell_idx);
+        }
+        tied
+    }
+
+    // MRV Heuristic: the undecided cells with the fewest remaining values
+    fn select_cells(&self) -> Vec<usize> {
         let mut min_count = 32;
-        let mut best_cell_idx = None;
+        let mut tied = Vec::new();
 
         for (i, cell) in self.grid.cells.iter().enumerate() {
             if cell.is_black { continue; }
             let count = cell.domain.count();
-            if count == 0 { return None; } // Should be caught by propagate
-            if count > 1 && count < min_count {
+            if count <= 1 || count > min_count { continue; }
+            if count < min_count {
                 min_count = count;
-                best_cell_idx = Some(i);
-                if count == 2 { break; } // Optimization: can't get better than 2
+                tied.clear();
             }
+            tied.push(i);
+            // Optimization: can't get better than 2, unless ties matter
+            if count == 2 && self.rng.is_none() { break; }
         }
+        tied
+    }
+
+    // Word and score for every completely filled slot of a grid.
+    pub fn slot_entries(&self, grid: &Grid) -> Vec<SlotEntry> {
+        self.slots
+            .iter()
+            .filter_map(|slot| {
+                let word: String = slot
+                    .cells
+                    .iter()
+                    .map(|&(r, c)| grid.get_cell(r, c).domain.is_singleton())
+                    .collect::<Option<String>>()?;
+                let score = match &self.locked[slot.id] {
+                    Some((_, score)) => *score,
+                    None => self.dawg.score(&word).unwrap_or(0),
+                };
+                Some(SlotEntry {
+                    id: slot.id,
+                    r: slot.r,
+                    c: slot.c,
+                    length: slot.length,
+                    is_across: slot.is_across,
+                    word,
+                    score,
+                })
+            })
+            .collect()
+    }
+}
+
+// The Luby sequence 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ... (1-based).
+fn luby(mut i: u64) -> u64 {
+    loop {
+        let mut k = 1;
+        while (1u64 << k) - 1 < i {
+            k += 1;
+        }
+        if (1u64 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i -= (1u64 << (k - 1)) - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::{solve_themed, ThemeWords, ThemedFill, UnplacedReason};
+    use rand::rngs::StdRng;
+    use rand::Rng;
+    use std::collections::BTreeSet;
+
+    // Words over A-C, so small grids have a handful of fills
+    const WORDS: [&str; 18] = [
+        "AB", "BA", "CA", "AC", "ABA", "ABC", "ACB", "BAA", "BAB", "BBC", "BCA", "CAB", "CBA", "CCA", "AAB", "BCC",
+        "CAA", "ACC",
+    ];
+
+    fn grid(width: usize, height: usize, black: &[(usize, usize)]) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for &(r, c) in black {
+            grid.set_black(r, c, true);
+        }
+        grid
+    }
+
+    // The entries of a filled grid, read off its rows and then its columns.
+    fn entries(grid: &Grid, letters: &[char]) -> Vec<String> {
+        let rows = (0..grid.height).map(|r| (0..grid.width).map(|c| letters[grid.get_index(r, c)]).collect::<String>());
+        let columns = (0..grid.width).map(|c| (0..grid.height).map(|r| letters[grid.get_index(r, c)]).collect::<String>());
+        rows.chain(columns)
+            .flat_map(|line| line.split('#').filter(|entry| entry.len() > 1).map(String::from).collect::<Vec<_>>())
+            .collect()
+    }
+
+    // Every fill of `grid` from `words`, found by trying each letter in
+    // every white square.
+    fn brute_force(grid: &Grid, words: &[&str], distinct: bool) -> BTreeSet<String> {
+        let white: Vec<usize> = (0..grid.cells.len()).filter(|&i| !grid.cells[i].is_black).collect();
+        let mut letters = vec!['#'; grid.cells.len()];
+        let mut fills = BTreeSet::new();
+        for n in 0..3usize.pow(white.len() as u32) {
+            let mut rest = n;
+            for &i in &white {
+                letters[i] = (b'A' + (rest % 3) as u8) as char;
+                rest /= 3;
+            }
+            let entries = entries(grid, &letters);
+            let unique: BTreeSet<&String> = entries.iter().collect();
+            if entries.iter().all(|e| words.contains(&e.as_str())) && (!distinct || unique.len() == entries.len()) {
+                fills.insert(letters.iter().collect());
+            }
+        }
+        fills
+    }
+
+    // A fill's letters in reading order, '#' for black squares.
+    fn spelled(fill: &Grid) -> String {
+        fill.cells
+            .iter()
+            .map(|cell| if cell.is_black { '#' } else { cell.domain.is_singleton().unwrap() })
+            .collect()
+    }
 
-        let cell_idx = match best_cell_idx {
-            Some(idx) => idx,
-            None => return Some(self.grid.clone()), // All cells are singletons
+    fn fills(grid: &Grid, words: &[&str], options: SolveOptions) -> BTreeSet<String> {
+        fills_from(grid, Rc::new(Dawg::from_words(words)), options)
+    }
+
+    fn fills_from(grid: &Grid, dawg: Rc<Dawg>, options: SolveOptions) -> BTreeSet<String> {
+        let mut solver = Solver::with_options(grid.clone(), dawg, options);
+        let mut fills = BTreeSet::new();
+        for fill in solver.solutions() {
+            assert!(fills.insert(spelled(&fill)), "a fill came up twice");
+        }
+        fills
+    }
+
+    fn small_grids() -> Vec<Grid> {
+        vec![grid(3, 3, &[]), grid(3, 3, &[(1, 1)]), grid(3, 3, &[(0, 0)]), grid(3, 3, &[(0, 0), (2, 2)])]
+    }
+
+    #[test]
+    fn fills_never_repeat_an_entry() {
+        for grid in small_grids() {
+            let expected = brute_force(&grid, &WORDS, true);
+            let found = fills(&grid, &WORDS, SolveOptions::default());
+            assert_eq!(found, expected);
+            for fill in &found {
+                let letters: Vec<char> = fill.chars().collect();
+                let entries = entries(&grid, &letters);
+                assert_eq!(entries.iter().collect::<BTreeSet<_>>().len(), entries.len(), "{}", fill);
+            }
+        }
+    }
+
+    #[test]
+    fn duplicates_can_be_allowed() {
+        let options = || SolveOptions {
+            allow_duplicates: true,
+            ..SolveOptions::default()
         };
+        for grid in small_grids() {
+            let expected = brute_force(&grid, &WORDS, false);
+            assert_eq!(fills(&grid, &WORDS, options()), expected);
+            assert!(expected.len() > brute_force(&grid, &WORDS, true).len());
+        }
 
-        // Try values
-        let current_domain = self.grid.cells[cell_idx].domain;
-        // Save state
-        let saved_grid = self.grid.clone();
+        // A symmetric square only has fills with the same word both ways
+        let square = grid(2, 2, &[]);
+        assert_eq!(fills(&square, &["AB", "BA"], options()).len(), 2);
+        let mut solver = Solver::new(square, Rc::new(Dawg::from_words(["AB", "BA"])));
+        assert!(matches!(solver.solve(), SolveOutcome::Unsatisfiable));
+    }
 
-        for char_val in current_domain.iter() {
-            // Assign value
-            self.grid.cells[cell_idx].domain = Domain::from_char(char_val);
-            
-            // Propagate
-            if self.propagate() {
-                if let Some(solution) = self.backtrack() {
-                    return Some(solution);
+    #[test]
+    fn scores_order_and_filter_fills() {
+        let scored = [("ABC", 10), ("BCA", 90), ("CAB", 40), ("ACB", 60)];
+        let dawg = Rc::new(Dawg::from_scored_words(scored));
+        let line = grid(3, 1, &[]);
+        let first = |options: SolveOptions| match Solver::with_options(line.clone(), dawg.clone(), options).solve() {
+            SolveOutcome::Solved(fill) => spelled(&fill),
+            _ => panic!("no fill"),
+        };
+        let alphabetical = || SolveOptions {
+            value_order: ValueOrder::Alphabetical,
+            ..SolveOptions::default()
+        };
+        assert_eq!(first(SolveOptions::default()), "BCA");
+        assert_eq!(first(alphabetical()), "ABC");
+        assert_eq!(
+            first(SolveOptions {
+                min_score: 50,
+                ..alphabetical()
+            }),
+            "ACB"
+        );
+
+        // Only the words scoring at least min_score fill a grid
+        let scored: Vec<(&str, u8)> = WORDS.iter().enumerate().map(|(i, &w)| (w, (i * 37 % 100) as u8)).collect();
+        let dawg = Rc::new(Dawg::from_scored_words(scored.iter().copied()));
+        let kept: Vec<&str> = scored.iter().filter(|(_, score)| *score >= 50).map(|&(w, _)| w).collect();
+        let mut found = 0;
+        for grid in small_grids() {
+            let options = SolveOptions {
+                min_score: 50,
+                ..SolveOptions::default()
+            };
+            let expected = brute_force(&grid, &kept, true);
+            assert_eq!(fills_from(&grid, dawg.clone(), options), expected);
+            assert!(expected.len() < brute_force(&grid, &WORDS, true).len());
+            found += expected.len();
+        }
+        assert!(found > 0);
+    }
+
+    // Whether a partial fill could still be part of a fill: its decided
+    // squares, fixed in a fresh grid, still propagate, and every slot it
+    // completes is a word.
+    fn consistent(grid: &Grid, partial: &Grid, dawg: &Rc<Dawg>) -> bool {
+        let mut fixed = grid.clone();
+        for (cell, decided) in fixed.cells.iter_mut().zip(&partial.cells) {
+            if cell.is_black {
+                continue;
+            }
+            if decided.domain.is_empty() {
+                return false;
+            }
+            if let Some(letter) = decided.domain.is_singleton() {
+                cell.domain = Domain::from_char(letter);
+            }
+        }
+        let mut solver = Solver::new(fixed, dawg.clone());
+        let complete = solver.slot_entries(partial);
+        solver.propagate() && complete.iter().all(|entry| dawg.is_word(&entry.word))
+    }
+
+    #[test]
+    fn each_budget_gives_up_with_a_consistent_partial() {
+        let words = four_letter_words(9, 40);
+        let dawg = Rc::new(Dawg::from_words(&words));
+        let open = grid(4, 4, &[]);
+        let budgets = [
+            (
+                Limit::Nodes,
+                SolveOptions {
+                    max_nodes: Some(5),
+                    ..SolveOptions::default()
+                },
+            ),
+            (
+                Limit::Backtracks,
+                SolveOptions {
+                    max_backtracks: Some(3),
+                    ..SolveOptions::default()
+                },
+            ),
+            (
+                Limit::Time,
+                SolveOptions {
+                    time_limit_ms: Some(0),
+                    ..SolveOptions::default()
+                },
+            ),
+            (Limit::Cancelled, SolveOptions::default()),
+        ];
+        for (limit, options) in budgets {
+            let mut solver = Solver::with_options(open.clone(), dawg.clone(), options.clone());
+            if limit == Limit::Cancelled {
+                solver.set_cancel_check(Rc::new(|| true));
+            }
+            let SolveOutcome::GaveUp { reason, best_partial } = solver.solve() else {
+                panic!("{:?} didn't stop the search", limit);
+            };
+            assert_eq!(reason, limit);
+            assert_eq!(solver.status(), SearchStatus::GaveUp(limit));
+            assert!(consistent(&open, &best_partial, &dawg), "{:?}", limit);
+            let stats = solver.stats();
+            assert!(options.max_nodes.is_none_or(|max| stats.nodes <= max));
+            assert!(options.max_backtracks.is_none_or(|max| stats.backtracks <= max));
+        }
+
+        // Without budgets the same search runs to the end
+        let mut solver = Solver::new(open, dawg);
+        assert!(!matches!(solver.solve(), SolveOutcome::GaveUp { .. }));
+        assert!(solver.stats().backtracks > 3);
+    }
+
+    #[test]
+    fn stepping_to_the_end_matches_solve() {
+        let dawg = Rc::new(Dawg::from_words(WORDS));
+        let mut grids = small_grids();
+        grids.push(grid(2, 2, &[]));
+        for grid in grids {
+            let mut whole = Solver::new(grid.clone(), dawg.clone());
+            let expected = match whole.solve() {
+                SolveOutcome::Solved(fill) => Some(spelled(&fill)),
+                _ => None,
+            };
+            for steps in [1, 3, 1000] {
+                let mut stepped = Solver::new(grid.clone(), dawg.clone());
+                let mut calls = 0;
+                let status = loop {
+                    calls += 1;
+                    match stepped.step(steps) {
+                        SearchStatus::Running => assert!(stepped.depth() <= grid.cells.len()),
+                        status => break status,
+                    }
+                };
+                assert_eq!(stepped.step(steps), status, "a finished search stays finished");
+                match &expected {
+                    Some(fill) => {
+                        assert_eq!(status, SearchStatus::Solved);
+                        assert_eq!(&spelled(stepped.grid()), fill);
+                    }
+                    None => assert_eq!(status, SearchStatus::Unsatisfiable),
+                }
+                assert_eq!(stepped.stats().nodes, whole.stats().nodes);
+                assert_eq!(stepped.stats().backtracks, whole.stats().backtracks);
+                if steps == 1 {
+                    assert!(calls > 1);
                 }
             }
+        }
+    }
+
+    #[test]
+    fn best_fills_are_sorted_and_counts_match_brute_force() {
+        let scored: Vec<(&str, u8)> = WORDS.iter().enumerate().map(|(i, &w)| (w, (i * 37 % 100) as u8)).collect();
+        let dawg = Rc::new(Dawg::from_scored_words(scored.iter().copied()));
+        let score = |word: &String| scored.iter().find(|(w, _)| w == word).unwrap().1 as u32;
+        for grid in small_grids() {
+            let expected = brute_force(&grid, &WORDS, true);
+            let mut totals: Vec<u32> = expected
+                .iter()
+                .map(|fill| entries(&grid, &fill.chars().collect::<Vec<_>>()).iter().map(score).sum())
+                .collect();
+            totals.sort_unstable_by(|a, b| b.cmp(a));
 
-            // Restore state for next iteration
-            self.grid = saved_grid.clone();
+            let counted = Solver::new(grid.clone(), dawg.clone()).count_solutions(usize::MAX);
+            assert_eq!(counted.count, expected.len());
+            assert!(counted.complete && counted.stopped.is_none());
+            let capped = Solver::new(grid.clone(), dawg.clone()).count_solutions(2);
+            assert_eq!(capped.count, expected.len().min(2));
+            assert_eq!(capped.complete, expected.len() < 2);
+
+            let mut solver = Solver::new(grid.clone(), dawg.clone());
+            let best = solver.best_solutions(3);
+            assert_eq!(best.count, expected.len());
+            let best_totals: Vec<u32> = best
+                .best
+                .iter()
+                .map(|fill| solver.slot_entries(fill).iter().map(|e| e.score as u32).sum())
+                .collect();
+            assert_eq!(best_totals, totals[..totals.len().min(3)]);
+            assert!(best.best.iter().all(|fill| expected.contains(&spelled(fill))));
         }
+    }
 
-        None
+    #[test]
+    fn an_exhausted_search_undoes_back_to_the_propagated_grid() {
+        let dawg = Rc::new(Dawg::from_words(WORDS));
+        let mut grids = small_grids();
+        grids.push(grid(2, 2, &[]));
+        for model in [SearchModel::Cell, SearchModel::Word] {
+            for grid in &grids {
+                let options = || SolveOptions {
+                    model,
+                    ..SolveOptions::default()
+                };
+                let mut fresh = Solver::with_options(grid.clone(), dawg.clone(), options());
+                let propagates = fresh.propagate();
+                let mut solver = Solver::with_options(grid.clone(), dawg.clone(), options());
+                // Each fill is the grid as the search stands when it is found
+                for fill in solver.solutions().take(3).collect::<Vec<_>>() {
+                    assert!(fill.cells.iter().all(|cell| cell.is_black || cell.domain.is_singleton().is_some()));
+                }
+                while matches!(solver.next_solution(), SolveOutcome::Solved(_)) {}
+                assert_eq!(solver.depth(), 0);
+                if propagates {
+                    let domains = |solver: &Solver| solver.grid().cells.iter().map(|cell| cell.domain).collect::<Vec<_>>();
+                    assert_eq!(domains(&solver), domains(&fresh));
+                }
+            }
+        }
     }
 
-    fn is_solved(&self) -> bool {
-        self.grid.cells.iter().all(|c| c.is_black || c.domain.count() == 1)
+    #[test]
+    fn every_heuristic_finds_every_fill() {
+        let expected: Vec<(Grid, BTreeSet<String>, BTreeSet<String>)> = small_grids()
+            .into_iter()
+            .map(|grid| {
+                let (distinct, repeated) = (brute_force(&grid, &WORDS, true), brute_force(&grid, &WORDS, false));
+                (grid, distinct, repeated)
+            })
+            .collect();
+        for model in [SearchModel::Cell, SearchModel::Word] {
+            for variable_order in [VariableOrder::Mrv, VariableOrder::DomWdeg, VariableOrder::SlotMrv] {
+                for value_order in [ValueOrder::Alphabetical, ValueOrder::Score, ValueOrder::Lcv] {
+                    let options = || SolveOptions {
+                        model,
+                        variable_order,
+                        value_order,
+                        ..SolveOptions::default()
+                    };
+                    for (grid, distinct, repeated) in &expected {
+                        let label = format!("{:?}", options());
+                        assert_eq!(&fills(grid, &WORDS, options()), distinct, "{}", label);
+                        let duplicates = SolveOptions {
+                            allow_duplicates: true,
+                            ..options()
+                        };
+                        assert_eq!(&fills(grid, &WORDS, duplicates), repeated, "{}", label);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_slot_option_leaves_the_grid_propagating() {
+        let dawg = Rc::new(Dawg::from_words(WORDS));
+        let mut fixed = grid(3, 3, &[(2, 2)]);
+        fixed.cells[0].domain = Domain::from_char('C');
+        fixed.cells[0].fixed_char = Some('C');
+        let mut grids = small_grids();
+        grids.push(fixed);
+        let mut listed = 0;
+        for grid in grids {
+            let base = Solver::new(grid.clone(), dawg.clone());
+            for slot in base.slots() {
+                let mut solver = base.clone();
+                let Some(options) = solver.slot_options(slot.id) else {
+                    continue;
+                };
+                assert!(options.complete);
+                let words: BTreeSet<&str> = options.words.iter().map(|option| option.word.as_str()).collect();
+                // Exactly the words that, locked in, leave the grid propagating
+                for &word in WORDS.iter().filter(|w| w.len() == slot.length) {
+                    let mut trial = base.clone();
+                    let fits = trial.lock_slot(slot.id, word, 0) && trial.propagate();
+                    assert_eq!(words.contains(word), fits, "{} in slot {}", word, slot.id);
+                }
+                listed += words.len();
+
+                // Trying them all leaves the grid as propagation left it
+                let mut fresh = base.clone();
+                fresh.propagate();
+                let domains = |solver: &Solver| solver.grid().cells.iter().map(|cell| cell.domain).collect::<Vec<_>>();
+                assert_eq!(domains(&solver), domains(&fresh));
+            }
+        }
+        assert!(listed > 0);
+    }
+
+    // Words of four letters over A-D.
+    fn four_letter_words(seed: u64, count: usize) -> Vec<String> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| (0..4).map(|_| (b'A' + rng.gen_range(0..4)) as char).collect())
+            .collect()
+    }
+
+    fn count(grid: &Grid, words: &[&str], options: SolveOptions) -> (usize, SolverStats) {
+        let mut solver = Solver::with_options(grid.clone(), Rc::new(Dawg::from_words(words)), options);
+        let counted = solver.count_solutions(usize::MAX);
+        assert!(counted.complete);
+        (counted.count, solver.take_stats())
+    }
+
+    fn search_options() -> Vec<SolveOptions> {
+        let mut all = Vec::new();
+        for model in [SearchModel::Cell, SearchModel::Word] {
+            for backjumping in [false, true] {
+                for learn_nogoods in [false, true] {
+                    all.push(SolveOptions {
+                        model,
+                        backjumping,
+                        learn_nogoods,
+                        ..SolveOptions::default()
+                    });
+                }
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn counts_fills_with_backjumping_and_nogoods_on_or_off() {
+        for grid in small_grids() {
+            let expected = brute_force(&grid, &WORDS, true);
+            for options in search_options() {
+                let label = format!("{:?}", options);
+                assert_eq!(fills(&grid, &WORDS, options.clone()), expected, "{}", label);
+                assert_eq!(count(&grid, &WORDS, options).0, expected.len(), "{}", label);
+            }
+        }
+
+        // Two 4x4 blocks, with dead ends the search has to learn from
+        let words = four_letter_words(11, 30);
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let split = grid(9, 4, &[(0, 4), (1, 4), (2, 4), (3, 4)]);
+        let plain = SolveOptions {
+            backjumping: false,
+            ..SolveOptions::default()
+        };
+        let (expected, _) = count(&split, &words, plain);
+        assert!(expected > 0);
+        let mut learned = false;
+        for options in search_options() {
+            let label = format!("{:?}", options);
+            let (found, stats) = count(&split, &words, options);
+            assert_eq!(found, expected, "{}", label);
+            learned |= stats.nogoods > 0;
+        }
+        assert!(learned);
+    }
+
+    #[test]
+    fn backjumping_skips_decisions_that_played_no_part() {
+        // A 3x3 block with plenty of fills above a 2x2 block that has none,
+        // since AB and BA would each fill it twice. The search fills the
+        // top block first; only backjumping sees that it isn't to blame.
+        let mut words = vec!["AB".to_string(), "BA".to_string()];
+        for bits in 0..8 {
+            words.push((0..3).map(|i| if bits >> i & 1 == 1 { 'B' } else { 'A' }).collect());
+        }
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let stacked = grid(3, 6, &[(3, 0), (3, 1), (3, 2), (4, 2), (5, 2)]);
+
+        let chronological = SolveOptions {
+            backjumping: false,
+            ..SolveOptions::default()
+        };
+        let (found, plain) = count(&stacked, &words, chronological);
+        assert_eq!(found, 0);
+        let (found, jumping) = count(&stacked, &words, SolveOptions::default());
+        assert_eq!(found, 0);
+        assert!(jumping.backjumps > 0);
+        assert!(jumping.backtracks < plain.backtracks);
+    }
+
+    #[test]
+    fn unsatisfiable_grids_have_no_fills() {
+        let square = grid(2, 2, &[]);
+        for options in search_options() {
+            assert_eq!(count(&square, &["AB", "BA"], options).0, 0);
+        }
+    }
+
+    fn with_restarts(restarts: Restarts, seed: u64) -> SolveOptions {
+        SolveOptions {
+            restarts,
+            restart_base: 1, // As often as possible
+            seed: Some(seed),
+            ..SolveOptions::default()
+        }
+    }
+
+    #[test]
+    fn counts_fills_with_restarts_on_or_off() {
+        let words = four_letter_words(11, 30);
+        let words: Vec<&str> = words.iter().map(String::as_str).collect();
+        let split = grid(9, 4, &[(0, 4), (1, 4), (2, 4), (3, 4)]);
+        let (expected, _) = count(&split, &words, SolveOptions::default());
+        let stacked = grid(3, 6, &[(3, 0), (3, 1), (3, 2), (4, 2), (5, 2)]);
+        let mut restarted = false;
+        for restarts in [Restarts::None, Restarts::Luby, Restarts::Geometric] {
+            for seed in 0..2 {
+                for grid in small_grids() {
+                    let label = format!("{:?} seed {}", restarts, seed);
+                    let expected = brute_force(&grid, &WORDS, true);
+                    assert_eq!(fills(&grid, &WORDS, with_restarts(restarts, seed)), expected, "{}", label);
+                }
+                let (found, stats) = count(&split, &words, with_restarts(restarts, seed));
+                assert_eq!(found, expected);
+                restarted |= stats.restarts > 0;
+                assert_eq!(count(&stacked, &["AB", "BA", "AAA", "BBB"], with_restarts(restarts, seed)).0, 0);
+            }
+        }
+        assert!(restarted);
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_search() {
+        let words = four_letter_words(11, 30);
+        let dawg = Rc::new(Dawg::from_words(&words));
+        let split = grid(9, 4, &[(0, 4), (1, 4), (2, 4), (3, 4)]);
+        let run = |seed| {
+            let mut solver = Solver::with_options(split.clone(), dawg.clone(), with_restarts(Restarts::Luby, seed));
+            let fill = match solver.solve() {
+                SolveOutcome::Solved(fill) => fill.cells.iter().map(|cell| cell.domain.is_singleton()).collect::<Vec<_>>(),
+                _ => panic!("no fill"),
+            };
+            let stats = solver.take_stats();
+            (fill, stats.nodes, stats.backtracks, stats.restarts)
+        };
+        assert_eq!(run(7), run(7));
+    }
+
+    // The slots a fresh solver would find, as their cells.
+    fn live_slots(solver: &Solver) -> BTreeSet<Vec<(usize, usize)>> {
+        (0..solver.slots.len())
+            .filter(|&i| !solver.ignored[i])
+            .map(|i| solver.slots[i].cells.clone())
+            .collect()
+    }
+
+    #[test]
+    fn place_black_matches_a_fresh_solver() {
+        let mut rng = StdRng::seed_from_u64(8);
+        // No three letter words, so squares that leave one are turned down
+        let mut words = four_letter_words(9, 120);
+        words.extend(["AB", "BA", "CA", "AC"].map(String::from));
+        let dawg = Rc::new(Dawg::from_words(&words));
+        let options = || SolveOptions {
+            backjumping: false,
+            ..SolveOptions::default()
+        };
+        let (mut agreed, mut rejected) = (0, 0);
+        for _ in 0..200 {
+            let mut grid = Grid::new(6, 6);
+            let mut solver: Option<Solver> = None;
+            for _ in 0..40 {
+                let (r, c) = (rng.gen_range(0..6), rng.gen_range(0..6));
+                if grid.get_cell(r, c).is_black {
+                    continue;
+                }
+                grid.set_black(r, c, true);
+                let mut fresh = Solver::with_options(grid.clone(), dawg.clone(), options());
+                let propagates = fresh.propagate();
+                let Some(incremental) = &mut solver else {
+                    solver = propagates.then_some(fresh);
+                    continue;
+                };
+                let placed = incremental.place_black(&[(r, c)]);
+                // It may turn down a square, but never lets a dead one in
+                assert!(propagates || !placed);
+                if placed {
+                    assert_eq!(live_slots(incremental), live_slots(&fresh));
+                    agreed += 1;
+                } else {
+                    grid.set_black(r, c, false);
+                    let before = Solver::with_options(grid.clone(), dawg.clone(), options());
+                    assert_eq!(live_slots(incremental), live_slots(&before));
+                    rejected += 1;
+                }
+                assert!(incremental.grid.cells.iter().zip(&grid.cells).all(|(a, b)| a.is_black == b.is_black));
+            }
+        }
+        assert!(agreed > 0 && rejected > 0);
+    }
+
+    fn theme(required: &[&str], preferred: &[&str]) -> ThemeWords {
+        ThemeWords {
+            required: required.iter().map(|w| w.to_string()).collect(),
+            preferred: preferred.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+
+    fn unplaced(fill: &ThemedFill) -> Vec<(&str, UnplacedReason)> {
+        fill.unplaced.iter().map(|u| (u.word.as_str(), u.reason)).collect()
+    }
+
+    #[test]
+    fn required_words_end_up_in_the_fill() {
+        let dawg = Rc::new(Dawg::from_words(WORDS));
+        let mut placed = 0;
+        for grid in small_grids() {
+            let fill = solve_themed(Solver::new(grid.clone(), dawg.clone()), &theme(&["CCC"], &[]));
+            let SolveOutcome::Solved(solution) = &fill.outcome else {
+                panic!("no fill");
+            };
+            let letters: Vec<char> = spelled(solution).chars().collect();
+            let words = entries(&grid, &letters);
+            // Every other entry comes from the dictionary
+            assert!(words.iter().filter(|w| *w != "CCC").all(|w| WORDS.contains(&w.as_str())));
+            if fill.placed.is_empty() {
+                assert_eq!(unplaced(&fill), [("CCC", UnplacedReason::NoSlot)]);
+                continue;
+            }
+            assert!(words.contains(&"CCC".to_string()));
+            assert!(fill.entries.iter().any(|e| e.word == "CCC"));
+            assert_eq!(fill.placed.iter().map(|p| p.word.as_str()).collect::<Vec<_>>(), ["CCC"]);
+            placed += 1;
+        }
+        assert!(placed > 0);
+    }
+
+    #[test]
+    fn a_required_word_moves_off_a_slot_with_no_fill() {
+        let words = ["CCC", "BCB", "BBC", "CBA", "ACB", "CCB", "BAC", "CAC"];
+        let base = Solver::new(Grid::new(3, 3), Rc::new(Dawg::from_words(words)));
+        // The top row takes CBC and still propagates, but has no fill with it
+        let mut first = base.clone();
+        assert!(first.lock_slot(0, "CBC", 50) && first.propagate());
+        assert!(matches!(first.solve(), SolveOutcome::Unsatisfiable));
+
+        let fill = solve_themed(base, &theme(&["CBC"], &[]));
+        assert!(matches!(fill.outcome, SolveOutcome::Solved(_)));
+        assert_eq!(fill.placed.len(), 1);
+        assert_ne!(fill.placed[0].slot, 0);
+        assert!(fill.unplaced.is_empty());
+    }
+
+    #[test]
+    fn preferred_words_are_dropped_before_the_fill_fails() {
+        let base = Solver::new(Grid::new(3, 3), Rc::new(Dawg::from_words(WORDS)));
+        let fill = solve_themed(base.clone(), &theme(&["AAA"], &[]));
+        let slot = fill.placed[0].slot;
+        // BBB fits somewhere alongside AAA, but no fill has both
+        let mut with_required = base.clone();
+        assert!(with_required.lock_slot(slot, "AAA", 50) && with_required.propagate());
+        assert!(with_required.slots().iter().any(|other| {
+            let mut trial = with_required.clone();
+            other.id != slot && trial.lock_slot(other.id, "BBB", 50) && trial.propagate()
+        }));
+
+        let fill = solve_themed(base, &theme(&["AAA"], &["BBB"]));
+        assert!(matches!(fill.outcome, SolveOutcome::Solved(_)));
+        assert_eq!(fill.placed.iter().map(|p| p.word.as_str()).collect::<Vec<_>>(), ["AAA"]);
+        assert_eq!(unplaced(&fill), [("BBB", UnplacedReason::NoFill)]);
+    }
+
+    #[test]
+    fn words_that_fit_no_slot_are_set_aside() {
+        let base = Solver::new(Grid::new(3, 3), Rc::new(Dawg::from_words(WORDS)));
+        let fill = solve_themed(base, &theme(&["ZZZZ", "CCC", "B4D"], &["QQQ"]));
+        assert!(matches!(fill.outcome, SolveOutcome::Solved(_)));
+        assert_eq!(fill.placed.iter().map(|p| p.word.as_str()).collect::<Vec<_>>(), ["CCC"]);
+        assert_eq!(
+            unplaced(&fill),
+            [("B4D", UnplacedReason::NotAWord), ("ZZZZ", UnplacedReason::NoSlot), ("QQQ", UnplacedReason::NoSlot)]
+        );
+    }
+
+    #[test]
+    fn theme_budgets_cover_every_attempt() {
+        // Seven words for six slots: no choice of slots has a fill
+        let required = ["AAA", "BBB", "CCC", "ABA", "BAB", "CAC", "ACA"];
+        let options = || SolveOptions {
+            max_nodes: Some(50),
+            ..SolveOptions::default()
+        };
+        let base = Solver::with_options(Grid::new(3, 3), Rc::new(Dawg::from_words(WORDS)), options());
+        let fill = solve_themed(base, &theme(&required, &[]));
+        assert!(matches!(fill.outcome, SolveOutcome::GaveUp { reason: Limit::Nodes, .. }));
+        assert!(fill.stats.nodes <= 50);
+        assert_eq!(fill.placed.len() + fill.unplaced.len(), required.len());
+        assert!(fill.unplaced.iter().all(|u| u.reason == UnplacedReason::GaveUp));
+
+        let unbounded = Solver::new(Grid::new(3, 3), Rc::new(Dawg::from_words(WORDS)));
+        let fill = solve_themed(unbounded, &theme(&required, &[]));
+        assert!(matches!(fill.outcome, SolveOutcome::Unsatisfiable));
+        assert!(fill.stats.nodes > 50);
     }
 }
@@ -0,0 +1,349 @@
+use crate::dawg::{parse_entry, Dawg};
+use crate::solver::{Limit, SlotEntry, SolveOutcome, Solver, SolverStats};
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+
+// The "Soul" of a puzzle: thematic words to fit before the skeleton fill.
+// Entries may carry a score (`WORD;SCORE`) like dictionary lines.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ThemeWords {
+    pub required: Vec<String>,
+    pub preferred: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ThemePlacement {
+    pub word: String,
+    pub slot: usize,
+    pub r: usize,
+    pub c: usize,
+    pub is_across: bool,
+}
+
+// Why a theme word was left out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnplacedReason {
+    NotAWord, // Not two or more letters A-Z
+    NoSlot,   // No open slot takes it, whatever the other theme words do
+    NoFill,   // It fits a slot, but not in any fill with the words placed
+    GaveUp,   // A budget ran out before it was placed
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Unplaced {
+    pub word: String,
+    pub reason: UnplacedReason,
+}
+
+pub struct ThemedFill {
+    pub outcome: SolveOutcome,
+    pub entries: Vec<SlotEntry>,
+    pub placed: Vec<ThemePlacement>,
+    pub unplaced: Vec<Unplaced>,
+    pub stats: SolverStats,
+}
+
+struct ThemeWord {
+    word: String,
+    score: u8,
+    required: bool,
+}
+
+// Places as many theme words as possible into `base`'s grid, then fills the
+// remaining slots from its dictionary.
+//
+// Words that fit no slot at all, even with every other theme word free to
+// go around them, are set aside first. Then required words go in, longest
+// first, each into a slot where it fits and the grid still propagates.
+// Every such slot is tried in turn: if no fill exists with a required word
+// where it was put, the word moves on to its next slot. Once the required
+// words are down, preferred words are placed greedily into the first slot
+// that works. While placing, open slots may also take theme words that are
+// still waiting, so an early placement isn't rejected just because a later
+// theme word is the only thing that fits next to it. If the skeleton fill
+// then fails, preferred words are dropped, most recently placed first, and
+// the fill is retried.
+//
+// Each slot tried counts as a node, one that doesn't fit as a backtrack,
+// and every fill attempt carries on the counts of those before it, so
+// `base`'s budgets bound the whole call.
+pub fn solve_themed(base: Solver, theme: &ThemeWords) -> ThemedFill {
+    let mut unplaced = Vec::new();
+    let mut words: Vec<ThemeWord> = Vec::new();
+    let entries = theme
+        .required
+        .iter()
+        .map(|w| (w, true))
+        .chain(theme.preferred.iter().map(|w| (w, false)));
+    for (raw, required) in entries {
+        match parse_entry(raw) {
+            Some((word, score))
+                if word.len() > 1 && word.chars().all(|c| c.is_ascii_alphabetic()) =>
+            {
+                let word = word.to_ascii_uppercase();
+                if !words.iter().any(|w| w.word == word) {
+                    words.push(ThemeWord { word, score, required });
+                }
+            }
+            _ => unplaced.push(Unplaced {
+                word: raw.clone(),
+                reason: UnplacedReason::NotAWord,
+            }),
+        }
+    }
+    words.sort_by_key(|w| (!w.required, std::cmp::Reverse(w.word.len())));
+
+    let mut placer = Placer {
+        base: &base,
+        words,
+        waiting: Vec::new(),
+        required: 0,
+        stats: base.stats().clone(),
+    };
+    match placer.set_aside_misfits() {
+        Ok(mut misfits) => unplaced.append(&mut misfits),
+        Err(reason) => {
+            unplaced.extend(placer.words.iter().map(|w| Unplaced {
+                word: w.word.clone(),
+                reason: UnplacedReason::GaveUp,
+            }));
+            return placer.gave_up(reason, &base, &[], unplaced);
+        }
+    }
+    placer.required = placer.words.iter().filter(|w| w.required).count();
+    // waiting[i]: the theme words still to be placed after word i
+    placer.waiting = (0..placer.words.len())
+        .map(|i| Rc::new(Dawg::from_words(placer.words[i + 1..].iter().map(|w| &w.word))))
+        .collect();
+
+    match placer.place_required(0, &base, &mut Vec::new()) {
+        Some(mut fill) => {
+            unplaced.append(&mut fill.unplaced);
+            fill.unplaced = unplaced;
+            fill
+        }
+        // Nothing is placed in a grid that has no fill
+        None => {
+            unplaced.extend(placer.words.iter().map(|w| Unplaced {
+                word: w.word.clone(),
+                reason: UnplacedReason::NoFill,
+            }));
+            ThemedFill {
+                outcome: SolveOutcome::Unsatisfiable,
+                entries: Vec::new(),
+                placed: Vec::new(),
+                unplaced,
+                stats: placer.stats,
+            }
+        }
+    }
+}
+
+struct Placer<'a> {
+    base: &'a Solver,
+    words: Vec<ThemeWord>,
+    waiting: Vec<Rc<Dawg>>,
+    required: usize, // words[..required] are the required ones
+    // Counts over everything tried so far, carried from one solver to the
+    // next so the budgets apply to the whole call
+    stats: SolverStats,
+}
+
+impl Placer<'_> {
+    // Drops the words no open slot of the bare grid takes, even with every
+    // other theme word allowed around them, and reports them.
+    fn set_aside_misfits(&mut self) -> Result<Vec<Unplaced>, Limit> {
+        let mut misfits = Vec::new();
+        let mut word_idx = 0;
+        while word_idx < self.words.len() {
+            let others = Rc::new(Dawg::from_words(
+                self.words.iter().enumerate().filter(|&(i, _)| i != word_idx).map(|(_, w)| &w.word),
+            ));
+            let mut fits = false;
+            for slot in self.open_slots(word_idx, self.base, &[]) {
+                if self.try_slot(word_idx, slot, self.base, others.clone())?.is_some() {
+                    fits = true;
+                    break;
+                }
+            }
+            if fits {
+                word_idx += 1;
+            } else {
+                misfits.push(Unplaced {
+                    word: self.words.remove(word_idx).word,
+                    reason: UnplacedReason::NoSlot,
+                });
+            }
+        }
+        Ok(misfits)
+    }
+
+    // Places the required words from `word_idx` on, trying each one in
+    // every slot it fits. None if no choice of slots leads to a fill.
+    fn place_required(&mut self, word_idx: usize, solver: &Solver, placements: &mut Vec<(usize, usize)>) -> Option<ThemedFill> {
+        if word_idx == self.required {
+            return self.place_preferred(solver, placements.clone());
+        }
+        for slot in self.open_slots(word_idx, solver, placements) {
+            let trial = match self.try_slot(word_idx, slot, solver, self.waiting[word_idx].clone()) {
+                Ok(Some(trial)) => trial,
+                Ok(None) => continue,
+                Err(reason) => return Some(self.gave_up(reason, solver, placements, self.left_after(placements))),
+            };
+            placements.push((word_idx, slot));
+            let fill = self.place_required(word_idx + 1, &trial, placements);
+            placements.pop();
+            if fill.is_some() {
+                return fill;
+            }
+        }
+        None
+    }
+
+    // Places each preferred word in the first slot it fits, then fills the
+    // rest. None if there is no fill even with every preferred word dropped.
+    fn place_preferred(&mut self, solver: &Solver, mut placements: Vec<(usize, usize)>) -> Option<ThemedFill> {
+        let mut unplaced = Vec::new();
+        let mut solver = solver.clone();
+        for word_idx in self.required..self.words.len() {
+            let mut placed = None;
+            for slot in self.open_slots(word_idx, &solver, &placements) {
+                match self.try_slot(word_idx, slot, &solver, self.waiting[word_idx].clone()) {
+                    Ok(Some(trial)) => {
+                        placed = Some((slot, trial));
+                        break;
+                    }
+                    Ok(None) => {}
+                    Err(reason) => {
+                        let mut left = unplaced;
+                        left.extend(self.left_after(&placements).into_iter().filter(|w| {
+                            !self.words[..word_idx].iter().any(|placed| placed.word == w.word)
+                        }));
+                        return Some(self.gave_up(reason, &solver, &placements, left));
+                    }
+                }
+            }
+            match placed {
+                Some((slot, trial)) => {
+                    placements.push((word_idx, slot));
+                    solver = trial;
+                }
+                None => unplaced.push(Unplaced {
+                    word: self.words[word_idx].word.clone(),
+                    reason: UnplacedReason::NoFill,
+                }),
+            }
+        }
+
+        loop {
+            // Replay the placements on a fresh solver so nothing pruned with the
+            // waiting theme words in play leaks into the skeleton fill.
+            let mut attempt = self.base.clone();
+            attempt.continue_stats(std::mem::take(&mut self.stats));
+            for &(word_idx, slot) in &placements {
+                let w = &self.words[word_idx];
+                attempt.lock_slot(slot, &w.word, w.score);
+            }
+            let outcome = attempt.solve();
+            self.stats = attempt.take_stats();
+            let entries = match &outcome {
+                SolveOutcome::Solved(solution) => attempt.slot_entries(solution),
+                SolveOutcome::GaveUp { best_partial, .. } => attempt.slot_entries(best_partial),
+                SolveOutcome::Unsatisfiable => Vec::new(),
+            };
+            if !matches!(outcome, SolveOutcome::Unsatisfiable) {
+                return Some(ThemedFill {
+                    outcome,
+                    entries,
+                    placed: describe(self.base, &self.words, &placements),
+                    unplaced,
+                    stats: self.stats.clone(),
+                });
+            }
+
+            let i = placements.iter().rposition(|&(word_idx, _)| !self.words[word_idx].required)?;
+            let (word_idx, _) = placements.remove(i);
+            unplaced.push(Unplaced {
+                word: self.words[word_idx].word.clone(),
+                reason: UnplacedReason::NoFill,
+            });
+        }
+    }
+
+    // Slots of the word's length that no theme word holds yet.
+    fn open_slots(&self, word_idx: usize, solver: &Solver, placements: &[(usize, usize)]) -> Vec<usize> {
+        let length = self.words[word_idx].word.len();
+        solver
+            .slots()
+            .iter()
+            .filter(|slot| slot.length == length && !placements.iter().any(|&(_, taken)| taken == slot.id))
+            .map(|slot| slot.id)
+            .collect()
+    }
+
+    // The solver with the word locked into `slot`, if the grid still
+    // propagates with `extra` words allowed in the open slots. Counts as
+    // a node; an error once a budget has run out.
+    fn try_slot(&mut self, word_idx: usize, slot: usize, solver: &Solver, extra: Rc<Dawg>) -> Result<Option<Solver>, Limit> {
+        let theme_word = &self.words[word_idx];
+        let mut trial = solver.clone();
+        trial.continue_stats(std::mem::take(&mut self.stats));
+        if let Err(reason) = trial.check_limits() {
+            self.stats = trial.take_stats();
+            return Err(reason);
+        }
+        trial.set_extra_words(Some(extra));
+        let fits = trial.lock_slot(slot, &theme_word.word, theme_word.score) && trial.propagate();
+        self.stats = trial.take_stats();
+        self.stats.nodes += 1;
+        if !fits {
+            self.stats.backtracks += 1;
+        }
+        trial.set_extra_words(None);
+        Ok(fits.then_some(trial))
+    }
+
+    // The words not among `placements`, for when placing stops short.
+    fn left_after(&self, placements: &[(usize, usize)]) -> Vec<Unplaced> {
+        (0..self.words.len())
+            .filter(|&i| !placements.iter().any(|&(placed, _)| placed == i))
+            .map(|i| Unplaced {
+                word: self.words[i].word.clone(),
+                reason: UnplacedReason::GaveUp,
+            })
+            .collect()
+    }
+
+    // What there is to hand back when a budget runs out while placing:
+    // the words down so far, in the grid as propagated around them.
+    fn gave_up(&self, reason: Limit, solver: &Solver, placements: &[(usize, usize)], unplaced: Vec<Unplaced>) -> ThemedFill {
+        ThemedFill {
+            outcome: SolveOutcome::GaveUp {
+                reason,
+                best_partial: solver.grid().clone(),
+            },
+            entries: solver.slot_entries(solver.grid()),
+            placed: describe(self.base, &self.words, placements),
+            unplaced,
+            stats: self.stats.clone(),
+        }
+    }
+}
+
+fn describe(solver: &Solver, words: &[ThemeWord], placements: &[(usize, usize)]) -> Vec<ThemePlacement> {
+    placements
+        .iter()
+        .map(|&(word_idx, slot_idx)| {
+            let slot = &solver.slots()[slot_idx];
+            ThemePlacement {
+                word: words[word_idx].word.clone(),
+                slot: slot.id,
+                r: slot.r,
+                c: slot.c,
+                is_across: slot.is_across,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,238 @@
+use crate::dawg::Dawg;
+use crate::domain::Domain;
+
+// Fixed-size set of small integers (word ids, decision levels).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitset {
+    blocks: Vec<u64>,
+}
+
+impl Bitset {
+    pub fn empty(len: usize) -> Self {
+        Bitset {
+            blocks: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    pub fn full(len: usize) -> Self {
+        let mut set = Self::empty(len);
+        for (i, block) in set.blocks.iter_mut().enumerate() {
+            let left = len - i * 64;
+            *block = if left >= 64 { u64::MAX } else { (1 << left) - 1 };
+        }
+        set
+    }
+
+    pub fn insert(&mut self, i: usize) {
+        self.blocks[i / 64] |= 1 << (i % 64);
+    }
+
+    pub fn remove(&mut self, i: usize) {
+        self.blocks[i / 64] &= !(1 << (i % 64));
+    }
+
+    pub fn contains(&self, i: usize) -> bool {
+        self.blocks[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn intersect_with(&mut self, other: &Bitset) {
+        for (a, b) in self.blocks.iter_mut().zip(&other.blocks) {
+            *a &= b;
+        }
+    }
+
+    pub fn union_with(&mut self, other: &Bitset) {
+        for (a, b) in self.blocks.iter_mut().zip(&other.blocks) {
+            *a |= b;
+        }
+    }
+
+    pub fn subtract(&mut self, other: &Bitset) {
+        for (a, b) in self.blocks.iter_mut().zip(&other.blocks) {
+            *a &= !b;
+        }
+    }
+
+    pub fn intersects(&self, other: &Bitset) -> bool {
+        self.blocks.iter().zip(&other.blocks).any(|(a, b)| a & b != 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.blocks.iter().map(|b| b.count_ones()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|&b| b == 0)
+    }
+
+    // Highest member
+    pub fn last(&self) -> Option<usize> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, &block)| block != 0)
+            .map(|(i, &block)| i * 64 + 63 - block.leading_zeros() as usize)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.blocks.iter().enumerate().flat_map(|(i, &block)| {
+            let mut rest = block;
+            std::iter::from_fn(move || {
+                if rest == 0 {
+                    return None;
+                }
+                let bit = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                Some(i * 64 + bit)
+            })
+        })
+    }
+}
+
+// The words of one length, alphabetical, with for every position and letter
+// the set of words that have that letter there.
+pub struct LengthIndex {
+    pub length: usize,
+    letters: Vec<u8>, // Word i is letters[i * length..(i + 1) * length]
+    scores: Vec<u8>,
+    // positions[pos * 26 + letter]
+    positions: Vec<Bitset>,
+    counts: Vec<u32>, // Sizes of `positions`
+}
+
+impl LengthIndex {
+    fn new(length: usize, words: &[(Vec<u8>, u8)]) -> Self {
+        let mut positions = vec![Bitset::empty(words.len()); length * 26];
+        let mut letters = Vec::with_capacity(words.len() * length);
+        for (id, (word, _)) in words.iter().enumerate() {
+            for (pos, &label) in word.iter().enumerate() {
+                positions[pos * 26 + label as usize].insert(id);
+            }
+            letters.extend_from_slice(word);
+        }
+        LengthIndex {
+            length,
+            letters,
+            scores: words.iter().map(|&(_, score)| score).collect(),
+            counts: positions.iter().map(|words| words.count()).collect(),
+            positions,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    // Letter indices (0 = A) of word `id`
+    pub fn word(&self, id: usize) -> &[u8] {
+        &self.letters[id * self.length..(id + 1) * self.length]
+    }
+
+    pub fn score(&self, id: usize) -> u8 {
+        self.scores[id]
+    }
+
+    pub fn find(&self, word: &[u8]) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            match self.word(mid).cmp(word) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    // Share of the words with `label` at `pos`.
+    pub fn frequency(&self, pos: usize, label: u8) -> f64 {
+        self.counts[pos * 26 + label as usize] as f64 / self.len() as f64
+    }
+
+    // Words whose every letter is allowed by the matching domain.
+    pub fn matching(&self, domains: &[Domain]) -> Bitset {
+        let mut words = Bitset::full(self.len());
+        for (pos, domain) in domains.iter().enumerate() {
+            let count = domain.count();
+            if count == 26 {
+                continue;
+            }
+            let at = &self.positions[pos * 26..(pos + 1) * 26];
+            // Whichever of the allowed or ruled-out letters is fewer
+            if count <= 13 {
+                let mut allowed = Bitset::empty(self.len());
+                for ch in domain.iter() {
+                    allowed.union_with(&at[(ch as u8 - b'A') as usize]);
+                }
+                words.intersect_with(&allowed);
+            } else {
+                for label in 0..26u8 {
+                    if !domain.contains((b'A' + label) as char) {
+                        words.subtract(&at[label as usize]);
+                    }
+                }
+            }
+        }
+        words
+    }
+
+    // Letters at `pos` across a set of words.
+    pub fn letters_at(&self, words: &Bitset, pos: usize) -> Domain {
+        let mut letters = Domain::empty();
+        for label in 0..26u8 {
+            if words.intersects(&self.positions[pos * 26 + label as usize]) {
+                letters = letters.union(Domain::from_char((b'A' + label) as char));
+            }
+        }
+        letters
+    }
+}
+
+// Positional letter index over a dictionary, for filtering slot
+// candidates with bitset operations instead of walking the DAWG.
+pub struct WordIndex {
+    by_length: Vec<Option<LengthIndex>>,
+}
+
+impl WordIndex {
+    // Indexes the words of `dawgs` (a word in several keeps its best score)
+    // that score at least `min_score`.
+    pub fn new(dawgs: &[&Dawg], min_score: u8) -> Self {
+        let mut by_length: Vec<Vec<(Vec<u8>, u8)>> = Vec::new();
+        for dawg in dawgs {
+            for (word, score) in dawg.scored_words() {
+                if score < min_score {
+                    continue;
+                }
+                let labels: Vec<u8> = word.bytes().map(|b| b - b'A').collect();
+                if by_length.len() <= labels.len() {
+                    by_length.resize(labels.len() + 1, Vec::new());
+                }
+                by_length[labels.len()].push((labels, score));
+            }
+        }
+        let by_length = by_length
+            .into_iter()
+            .enumerate()
+            .map(|(length, mut words)| {
+                if words.is_empty() {
+                    return None;
+                }
+                words.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+                words.dedup_by(|a, b| a.0 == b.0);
+                Some(LengthIndex::new(length, &words))
+            })
+            .collect();
+        WordIndex { by_length }
+    }
+
+    pub fn length(&self, length: usize) -> Option<&LengthIndex> {
+        self.by_length.get(length).and_then(|index| index.as_ref())
+    }
+}